@@ -12,7 +12,7 @@ use bincode::{serialize, deserialize, Infinite};
 pub trait LoadStore: Sized {
     fn load<P>(path: P) -> Option<Self> where P: AsRef<Path>;
     fn store<P>(&self, path: P) where P: AsRef<Path>;
-    fn store_tga<P>(self, path: P) where P: AsRef<Path>;
+    fn store_tga<P>(self, path: P, balance: &WhiteBalance) where P: AsRef<Path>;
 }
 
 impl LoadStore for Image {
@@ -21,7 +21,8 @@ impl LoadStore for Image {
             Ok(mut file) => {
                 let mut data = Vec::new();
                 file.read_to_end(&mut data).unwrap();
-                Some(deserialize(data.as_slice()).unwrap())
+                // the file of the older format is started over
+                deserialize(data.as_slice()).ok()
             }
             Err(_) => None
         }
@@ -30,14 +31,17 @@ impl LoadStore for Image {
     fn store<P>(&self, path: P) where P: AsRef<Path> {
         let image_encoded: Vec<u8> = serialize(&self, Infinite).unwrap();
         let mut file = File::create(path).unwrap();
-        file.write(image_encoded.as_slice()).unwrap();
+        file.write_all(image_encoded.as_slice()).unwrap();
     }
 
-    fn store_tga<P>(self, path: P) where P: AsRef<Path> {
+    fn store_tga<P>(self, path: P, balance: &WhiteBalance) where P: AsRef<Path> {
         let format = self.size();
         let image_header = TgaHeader::rgb(format.horizontal_count, format.vertical_count);
         let mut file = File::create(path).unwrap();
-        file.write(serialize(&image_header, Infinite).unwrap().as_slice()).unwrap();
-        file.write(self.bitmap(10.0).as_slice()).unwrap();
+        file.write_all(serialize(&image_header, Infinite).unwrap().as_slice()).unwrap();
+        let bitmap = self.balance(balance)
+            .and_then(|image| image.bitmap(10.0, ColorSpace::Srgb))
+            .expect("observer image");
+        file.write_all(bitmap.as_slice()).unwrap();
     }
 }
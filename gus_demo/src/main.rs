@@ -21,13 +21,16 @@ use self::tracer::Tracer;
 use self::image::LoadStore;
 
 pub fn main() {
+    let gray = Beam::red() + Beam::green() + Beam::blue();
+    // daylight as bright as the gray lamp the demo used before
+    let light = Beam::d65().with_luminance(gray.luminance());
+
     let scene = {
-        let gray = Beam::red() + Beam::green() + Beam::blue();
 
         let d_rg = Material::diffuse(Beam::red() + Beam::green());
         let d_gb = Material::diffuse(Beam::green() + Beam::blue());
         let d_br = Material::diffuse(Beam::blue() + Beam::red());
-        let e_w = Material::emission(gray.clone());
+        let e_w = Material::emission(light.clone());
         let d_gray = Material::diffuse(gray.clone() * 0.5);
 
         let dr = Material::diffuse(gray.clone() * 0.01) + Material::reflection(gray.clone() * 0.9);
@@ -71,6 +74,8 @@ pub fn main() {
             width: 1.6,
             height: 0.9,
             distance: 1.5,
+
+            polarizer: None,
         };
 
         Screen::new(format.clone(), eye)
@@ -80,8 +85,9 @@ pub fn main() {
     let path_backup = "out.backup.bgus";
     let tga_path = "out.tga";
 
+    // the image of the other screen is started over
+    let image = Image::load(path).filter(|image| image.size() == screen.size());
     let mut tracer = Tracer::new(scene, screen);
-    let image = Image::load(path);
     tracer.start(8, image, |tid, sample, elapsed_time| {
         println!("thread: {:?}, sample: {:?}, elapsed_time: {:?}", tid, sample, elapsed_time);
     });
@@ -98,5 +104,6 @@ pub fn main() {
     let _ = fs::remove_file(path_backup);
     let _ = fs::rename(path, path_backup);
     result_image.store(path);
-    result_image.store_tga(tga_path);
+    let balance = WhiteBalance::new(&light, &Observer::default(), Adaptation::default());
+    result_image.store_tga(tga_path, &balance);
 }
@@ -29,7 +29,7 @@ impl Tracer {
             where Report: Fn(usize, usize, f64) + Send + Sync + 'static {
         let report = Arc::new(report);
         self.image = image;
-        self.threads = (0..number_of_threads).into_iter().map(|i| {
+        self.threads = (0..number_of_threads).map(|i| {
             let (tx, rx) = mpsc::channel();
             let scene_ref_clone = self.scene.clone();
             let screen_ref_clone = self.screen.clone();
@@ -44,14 +44,14 @@ impl Tracer {
                 let mut j = 0usize;
                 let mut last: f64 = time();
                 loop {
-                    screen_ref_clone.sample(&*scene_ref_clone, &mut image, &mut rng);
+                    screen_ref_clone.sample(&scene_ref_clone, &mut image, &mut rng);
                     let temp = time();
                     report_ref_clone(i, j, temp - last);
                     last = temp;
-                    j = j + 1;
+                    j += 1;
 
                     match rx.try_recv() {
-                        Ok(_) | Err(mpsc::TryRecvError::Disconnected) => break (),
+                        Ok(_) | Err(mpsc::TryRecvError::Disconnected) => break,
                         Err(mpsc::TryRecvError::Empty) => ()
                     }
                 }
@@ -61,7 +61,7 @@ impl Tracer {
     }
 
     pub fn stop(self) -> Image {
-        for &(_, ref tx) in self.threads.iter() {
+        for (_, tx) in self.threads.iter() {
             tx.send(()).unwrap();
         }
 
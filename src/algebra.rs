@@ -22,6 +22,18 @@ impl V3 {
         V3 { x: x, y: y, z: z }
     }
 
+    pub fn x(self) -> M {
+        self.x
+    }
+
+    pub fn y(self) -> M {
+        self.y
+    }
+
+    pub fn z(self) -> M {
+        self.z
+    }
+
     pub fn length(self) -> M {
         (self * self).sqrt()
     }
@@ -38,6 +50,17 @@ impl V3 {
         }
     }
 
+    /// two unit vectors orthogonal to the unit vector and to each other
+    pub fn basis(self) -> (Self, Self) {
+        let axis = if self.x.abs() < 0.9 {
+            V3::new(1.0, 0.0, 0.0)
+        } else {
+            V3::new(0.0, 1.0, 0.0)
+        };
+        let tangent = self.cross(axis).normalize();
+        (tangent, self.cross(tangent))
+    }
+
     pub fn adj(a: Self, b: Self, c: Self) -> (Self, Self, Self) {
         let ia = V3::new(b.y * c.z - b.z * c.y, b.z * c.x - b.x * c.z, b.x * c.y - b.y * c.x);
         let ib = V3::new(c.y * a.z - c.z * a.y, c.z * a.x - c.x * a.z, c.x * a.y - c.y * a.x);
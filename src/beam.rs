@@ -10,100 +10,182 @@ use super::color;
 
 use super::algebra::M;
 
-const SIZE: usize = 24;
+use super::spectrum;
+use super::spectrum::Spectrum;
 
-/// Frequency struct is index in table
-#[derive(Clone)]
+use super::film::ThinFilm;
+use super::fluorescence::Fluorescence;
+use super::grating::Grating;
+use super::polarization::PolarizingFilter;
+
+/// Frequency struct is index of the band in the current spectrum
+#[derive(Clone, Debug, PartialEq)]
 pub struct Frequency {
     index: usize,
 }
 
 impl Frequency {
     pub fn new(index: usize) -> Self {
-        Frequency { index: index % Beam::SIZE }
+        Frequency { index: index % Spectrum::current().size() }
+    }
+
+    /// the band containing the wavelength in nanometres, none if it is out of the spectrum
+    pub fn from_wavelength(wavelength: M) -> Option<Self> {
+        Spectrum::current().index(wavelength).map(|index| Frequency { index: index })
+    }
+
+    /// all bands of the current spectrum from the shortest wavelength to the longest
+    pub fn all() -> impl Iterator<Item = Frequency> {
+        (0..Spectrum::current().size()).map(|index| Frequency { index: index })
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// wavelength of the centre of the band in nanometres
+    pub fn wavelength(&self) -> M {
+        Spectrum::current().wavelength(self.index)
+    }
+
+    /// shortest and longest wavelength of the band in nanometres
+    pub fn bounds(&self) -> (M, M) {
+        Spectrum::current().bounds(self.index)
+    }
+
+    /// evenly spaced wavelengths in nanometres `Beam::from_function` averages over the band
+    pub(crate) fn samples(&self) -> impl ExactSizeIterator<Item = M> {
+        let count = 16;
+        let (shortest, longest) = self.bounds();
+        (0..count).map(move |k| shortest + (longest - shortest) * ((k as M) + 0.5) / (count as M))
     }
 }
 
 /// Density is a number of particles in ray
-
 pub type Density = f64;
 
 /// Factor is a refraction coefficient
-
 pub type Factor = M;
 
 /// Fate
-
 pub enum SingleFate {
     Decay,
     Diffuse,
     Reflect,
     Refract(Factor),
+    Interfere(ThinFilm),
+    Fluoresce(Frequency),
+    Diffract(Grating),
 }
 
 pub struct Fate {
-    pub emission: bool,
     pub single: SingleFate,
-}
-
-fn fate(value: Density, mut rng: &mut Rng) -> bool {
-    Range::new(0.0 as Density, 1.0).sample(&mut rng) < value
+    pub filter: Option<PolarizingFilter>,
+    /// probability that the ray is not absorbed, it is the throughput of the chosen fate
+    /// at the band of the ray, the fate itself is still chosen randomly
+    pub weight: Density,
 }
 
 impl SingleFate {
 
-    fn new(
-        factor: Factor,
-        diffuse: Density,
-        reflect: Density,
-        refract: Density,
-        mut rng: &mut Rng,
-    ) -> Self {
+    // the `fate` is a uniform random number below the survival probability of the material
+    fn new(material: &Material, frequency: &Frequency, fate: Density) -> Self {
         use self::SingleFate::*;
 
-        let fate = Range::new(0.0 as Density, 1.0).sample(&mut rng);
+        let factor = material.refraction_factor.factor(frequency);
+        let diffuse = material.diffuse.density(frequency);
+        let reflect = material.reflection.density(frequency);
+        let refract = material.refraction.density(frequency);
+
+        let excitation = match material.fluorescence {
+            Some(ref fluorescence) => fluorescence.efficiency(frequency),
+            None => 0.0,
+        };
+
+        // the ranges of the fates follow each other, the fluorescence takes its efficiency
         if fate < diffuse {
             Diffuse
-        } else if fate < diffuse + reflect {
-            Reflect
-        } else if fate < diffuse + reflect + refract {
-            Refract(factor)
+        } else if fate < diffuse + excitation {
+            material
+                .fluorescence
+                .as_ref()
+                .and_then(|fluorescence| fluorescence.excitation(frequency, fate - diffuse))
+                .map(Fluoresce)
+                .unwrap_or(Decay)
         } else {
-            Decay
+            // the film and the grating take the part of the scattering fates
+            // and decide themselves where the ray goes
+            let fate = fate - diffuse - excitation;
+            if fate >= reflect + refract {
+                Decay
+            } else if let Some(ref film) = material.film {
+                Interfere(film.clone())
+            } else if let Some(ref grating) = material.grating {
+                Diffract(grating.clone())
+            } else if fate < reflect {
+                Reflect
+            } else {
+                Refract(factor)
+            }
         }
     }
 }
 
 /// Beam struct is a compound of different Photons
-#[derive(Default, Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Beam {
-    powers: [Density; SIZE],
+    #[serde(deserialize_with = "spectrum::bands")]
+    powers: Vec<Density>,
 }
 
 impl Beam {
-    pub const SIZE: usize = SIZE;
-
     #[inline(always)]
     fn populate(index: usize) -> Self {
-        let mut powers = [0.0; Self::SIZE];
-        for i in 0..Self::SIZE {
-            let value = {
-                let j = (i * color::TABLE_SIZE) / Self::SIZE;
-                let table = color::table();
-                let rgb = table[j].1.clone();
+        let spectrum = Spectrum::current();
+        let powers = (0..spectrum.size())
+            .map(|i| {
+                let rgb = color::interpolate(spectrum.wavelength(i));
                 match index {
                     0 => rgb.r,
                     1 => rgb.g,
                     _ => rgb.b
                 }
-            };
-
-            powers[i] = value;
-        }
+            })
+            .collect();
 
+        // the spectrum outside the color table leaves the beam dark
         let beam = Beam { powers: powers };
         let length = (beam.clone() * &beam).sqrt();
-        beam * (1.0 / length)
+        if length > 0.0 {
+            beam * (1.0 / length)
+        } else {
+            beam
+        }
+    }
+
+    /// spectrum given by the function of the wavelength in nanometres averaged over every band
+    pub fn from_function<F>(f: F) -> Self
+    where
+        F: Fn(M) -> Density,
+    {
+        Self::from_bands(|frequency| {
+            let samples = frequency.samples();
+            let count = samples.len();
+            samples.fold(0.0, |sum, wavelength| sum + f(wavelength)) / (count as Density)
+        })
+    }
+
+    /// spectrum given by the function of the band
+    pub fn from_bands<F>(mut f: F) -> Self
+    where
+        F: FnMut(&Frequency) -> Density,
+    {
+        Beam { powers: Frequency::all().map(|frequency| f(&frequency)).collect() }
+    }
+
+    /// spectrum with the same density at every band
+    pub fn flat(density: Density) -> Self {
+        Beam { powers: vec![density; Spectrum::current().size()] }
     }
 
     #[inline(always)]
@@ -121,8 +203,26 @@ impl Beam {
         Self::populate(2)
     }
 
-    fn density(&self, frequency: &Frequency) -> Density {
-        self.powers[frequency.index]
+    pub fn density(&self, frequency: &Frequency) -> Density {
+        self.power(frequency.index)
+    }
+
+    fn power(&self, index: usize) -> Density {
+        self.powers[index]
+    }
+
+    fn zip<F>(&self, rhs: &Beam, f: F) -> Self
+    where
+        F: Fn(Density, Density) -> Density,
+    {
+        let size = Spectrum::current().size();
+        Beam { powers: (0..size).map(|i| f(self.power(i), rhs.power(i))).collect() }
+    }
+}
+
+impl Default for Beam {
+    fn default() -> Self {
+        Self::flat(0.0)
     }
 }
 
@@ -130,12 +230,7 @@ impl<'a> Mul<&'a Beam> for Beam {
     type Output = Density;
 
     fn mul(self, rhs: &'a Self) -> Self::Output {
-        let mut product = 0.0;
-        for i in 0..Self::SIZE {
-            product = product + self.powers[i] * rhs.powers[i];
-        }
-
-        product
+        self.zip(rhs, |a, b| a * b).powers.iter().sum()
     }
 }
 
@@ -143,13 +238,7 @@ impl Mul<Density> for Beam {
     type Output = Beam;
 
     fn mul(self, rhs: Density) -> Self::Output {
-        let mut powers = [0.0; Self::SIZE];
-
-        for i in 0..Self::SIZE {
-            powers[i] = self.powers[i] * rhs;
-        }
-
-        Beam { powers: powers }
+        Beam { powers: self.powers.iter().map(|power| power * rhs).collect() }
     }
 }
 
@@ -157,13 +246,7 @@ impl Add<Beam> for Beam {
     type Output = Self;
 
     fn add(self, rhs: Beam) -> Self::Output {
-        let mut powers = [0.0; Self::SIZE];
-
-        for i in 0..Self::SIZE {
-            powers[i] = self.powers[i] + rhs.powers[i];
-        }
-
-        Beam { powers: powers }
+        self.zip(&rhs, |a, b| a + b)
     }
 }
 
@@ -171,28 +254,50 @@ impl Add<Frequency> for Beam {
     type Output = Self;
 
     fn add(self, rhs: Frequency) -> Self::Output {
-        let Beam { powers: mut powers } = self;
-        powers[rhs.index] = powers[rhs.index] + 1.0;
-        Beam { powers: powers }
+        let mut beam = self.zip(&Beam::default(), |a, _| a);
+        beam.powers[rhs.index] += 1.0;
+        beam
     }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct BeamRefract {
-    powers: [Factor; SIZE],
+    #[serde(deserialize_with = "spectrum::bands")]
+    powers: Vec<Factor>,
 }
 
 impl BeamRefract {
-    pub const SIZE: usize = SIZE;
-
     pub fn identity() -> Self {
         BeamRefract {
-            powers: [1.0; SIZE]
+            powers: vec![1.0; Spectrum::current().size()]
+        }
+    }
+
+    /// the ray enters the medium from the air, so the factor is inverse of the refraction index,
+    /// the `index` is evaluated at the centre of every band, the wavelength is in nanometres
+    pub fn from_index<F>(index: F) -> Self
+    where
+        F: Fn(M) -> M,
+    {
+        Self::from_band_index(|frequency| index(frequency.wavelength()))
+    }
+
+    /// refraction index given by the function of the band
+    pub fn from_band_index<F>(index: F) -> Self
+    where
+        F: Fn(&Frequency) -> M,
+    {
+        BeamRefract {
+            powers: Frequency::all().map(|frequency| 1.0 / index(&frequency)).collect(),
         }
     }
 
-    fn factor(&self, frequency: &Frequency) -> Factor {
-        self.powers[frequency.index]
+    pub fn factor(&self, frequency: &Frequency) -> Factor {
+        self.power(frequency.index)
+    }
+
+    fn power(&self, index: usize) -> Factor {
+        self.powers[index]
     }
 }
 
@@ -206,13 +311,7 @@ impl Mul<Factor> for BeamRefract {
     type Output = BeamRefract;
 
     fn mul(self, rhs: Factor) -> Self::Output {
-        let mut powers = [0.0; Self::SIZE];
-
-        for i in 0..Self::SIZE {
-            powers[i] = self.powers[i] * rhs;
-        }
-
-        BeamRefract { powers: powers }
+        BeamRefract { powers: self.powers.iter().map(|power| power * rhs).collect() }
     }
 }
 
@@ -220,13 +319,8 @@ impl Add<BeamRefract> for BeamRefract {
     type Output = Self;
 
     fn add(self, rhs: BeamRefract) -> Self::Output {
-        let mut powers = [0.0; Self::SIZE];
-
-        for i in 0..Self::SIZE {
-            powers[i] = self.powers[i] + rhs.powers[i];
-        }
-
-        BeamRefract { powers: powers }
+        let size = Spectrum::current().size();
+        BeamRefract { powers: (0..size).map(|i| self.power(i) + rhs.power(i)).collect() }
     }
 }
 
@@ -238,6 +332,10 @@ pub struct Material {
     reflection: Beam,
     refraction: Beam,
     refraction_factor: BeamRefract,
+    film: Option<ThinFilm>,
+    fluorescence: Option<Fluorescence>,
+    grating: Option<Grating>,
+    filter: Option<PolarizingFilter>,
 }
 
 impl Material {
@@ -248,6 +346,10 @@ impl Material {
             reflection: Beam::default(),
             refraction: Beam::default(),
             refraction_factor: BeamRefract::default(),
+            film: None,
+            fluorescence: None,
+            grating: None,
+            filter: None,
         }
     }
 
@@ -258,6 +360,10 @@ impl Material {
             reflection: Beam::default(),
             refraction: Beam::default(),
             refraction_factor: BeamRefract::default(),
+            film: None,
+            fluorescence: None,
+            grating: None,
+            filter: None,
         }
     }
 
@@ -268,9 +374,14 @@ impl Material {
             reflection: beam,
             refraction: Beam::default(),
             refraction_factor: BeamRefract::default(),
+            film: None,
+            fluorescence: None,
+            grating: None,
+            filter: None,
         }
     }
 
+    /// glass-like surface which splits the ray by Fresnel equations
     pub fn refraction(beam: Beam, factor: BeamRefract) -> Self {
         Material {
             emission: Beam::default(),
@@ -278,19 +389,104 @@ impl Material {
             reflection: Beam::default(),
             refraction: beam,
             refraction_factor: factor,
+            film: None,
+            fluorescence: None,
+            grating: None,
+            filter: None,
         }
     }
 
-    pub fn fate(&self, frequency: &Frequency, mut rng: &mut Rng) -> Fate {
+    /// the film scatters every ray, it takes the reflection and the refraction
+    /// of the sum with other materials
+    pub fn film(film: ThinFilm) -> Self {
+        Material {
+            emission: Beam::default(),
+            diffuse: Beam::default(),
+            reflection: Beam::default(),
+            refraction: Beam::flat(1.0),
+            refraction_factor: BeamRefract::default(),
+            film: Some(film),
+            fluorescence: None,
+            grating: None,
+            filter: None,
+        }
+    }
+
+    pub fn fluorescence(fluorescence: Fluorescence) -> Self {
+        Material {
+            emission: Beam::default(),
+            diffuse: Beam::default(),
+            reflection: Beam::default(),
+            refraction: Beam::default(),
+            refraction_factor: BeamRefract::default(),
+            film: None,
+            fluorescence: Some(fluorescence),
+            grating: None,
+            filter: None,
+        }
+    }
+
+    pub fn grating(grating: Grating) -> Self {
+        Material {
+            emission: Beam::default(),
+            diffuse: Beam::default(),
+            reflection: Beam::flat(1.0),
+            refraction: Beam::default(),
+            refraction_factor: BeamRefract::default(),
+            film: None,
+            fluorescence: None,
+            grating: Some(grating),
+            filter: None,
+        }
+    }
+
+    /// thin sheet transmitting the ray through the filter
+    pub fn filter(filter: PolarizingFilter) -> Self {
+        Material {
+            emission: Beam::default(),
+            diffuse: Beam::default(),
+            reflection: Beam::default(),
+            refraction: Beam::flat(1.0),
+            refraction_factor: BeamRefract::default(),
+            film: None,
+            fluorescence: None,
+            grating: None,
+            filter: Some(filter),
+        }
+    }
+
+    pub fn emission_density(&self, frequency: &Frequency) -> Density {
+        self.emission.density(frequency)
+    }
+
+    pub fn diffuse_density(&self, frequency: &Frequency) -> Density {
+        self.diffuse.density(frequency)
+    }
+
+    /// probability that the ray is not absorbed at the `frequency`
+    pub fn survival(&self, frequency: &Frequency) -> Density {
+        let excitation = match self.fluorescence {
+            Some(ref fluorescence) => fluorescence.efficiency(frequency),
+            None => 0.0,
+        };
+        let scattering = self.reflection.density(frequency) + self.refraction.density(frequency);
+        (self.diffuse.density(frequency) + excitation + scattering).min(1.0)
+    }
+
+    /// fate of the ray chosen among the ones it survives, so only the material
+    /// absorbing everything decays, the absorption is in the `weight` of the fate
+    pub fn fate(&self, frequency: &Frequency, mut rng: &mut dyn Rng) -> Fate {
+        let weight = self.survival(frequency);
+        let single = if weight > 0.0 {
+            SingleFate::new(self, frequency, Range::new(0.0, weight).sample(&mut rng))
+        } else {
+            SingleFate::Decay
+        };
+
         Fate {
-            emission: fate(self.emission.density(frequency), &mut rng),
-            single: SingleFate::new(
-                self.refraction_factor.factor(frequency),
-                self.diffuse.density(frequency),
-                self.reflection.density(frequency),
-                self.refraction.density(frequency),
-                &mut rng,
-            ),
+            single: single,
+            filter: self.filter.clone(),
+            weight: weight,
         }
     }
 }
@@ -305,11 +501,16 @@ impl Add for Material {
             reflection: self.reflection + rhs.reflection,
             refraction: self.refraction + rhs.refraction,
             refraction_factor: self.refraction_factor + rhs.refraction_factor,
+            film: self.film.or(rhs.film),
+            fluorescence: self.fluorescence.or(rhs.fluorescence),
+            grating: self.grating.or(rhs.grating),
+            filter: self.filter.or(rhs.filter),
         }
     }
 }
 
 /// RGB struct to pass on the screen
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Default, Clone, Serialize, Deserialize)]
 pub struct RGB {
     r: Density,
@@ -322,6 +523,18 @@ impl RGB {
         RGB { r: r, g: g, b: b }
     }
 
+    pub fn components(&self) -> (Density, Density, Density) {
+        (self.r, self.g, self.b)
+    }
+
+    /// apply the function to every component
+    pub fn map<F>(self, f: F) -> Self
+    where
+        F: Fn(Density) -> Density,
+    {
+        RGB::new(f(self.r), f(self.g), f(self.b))
+    }
+
     pub fn update_raw(self, raw: &mut Vec<u8>) {
         let to_byte = |a: Density| -> u8 {
             if a > 1.0 {
@@ -0,0 +1,642 @@
+use super::algebra::V3;
+use super::algebra::M_PI;
+
+use super::beam::Density;
+use super::beam::Frequency;
+
+use super::bsdf::Bsdf;
+use super::bsdf::Surface;
+
+use super::ray;
+use super::ray::Ray;
+use super::ray::PhotonicRay;
+use super::ray::GeometricalRay;
+
+use super::scene::Scene;
+use super::screen::Screen;
+
+use super::integrator::Depth;
+use super::integrator::Integrator;
+
+use std::ptr;
+
+use rand::Rng;
+use rand::distributions::Sample;
+use rand::distributions::Range;
+
+// vertex of the path, the eye has no surface, the normal is of unit length
+#[derive(Clone)]
+pub(crate) struct Vertex<'a> {
+    pub(crate) position: V3,
+    pub(crate) normal: V3,
+    pub(crate) surface: Option<&'a Surface>,
+}
+
+// vertex of the light subpath along with the throughput of the light arriving there
+pub(crate) struct LightVertex<'a> {
+    pub(crate) vertex: Vertex<'a>,
+    pub(crate) throughput: Density,
+}
+
+/// Bidirectional struct is the setting of the bidirectional path tracing, the subpath
+/// from the random emitter is connected to every vertex of the subpath from the eye
+/// and to the eye itself
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Bidirectional {
+    #[serde(default)]
+    depth: Depth,
+}
+
+impl Bidirectional {
+    /// the same depth limits both subpaths
+    pub fn with_depth(self, depth: Depth) -> Self {
+        Bidirectional { depth: depth }
+    }
+}
+
+/// the pass of the screen also connects the light subpaths to the eye directly,
+/// the polarizer of the eye needs the polarization of the light, so the screen with it
+/// and the rays not from the eye of the screen do not use these strategies
+impl Integrator for Bidirectional {
+    fn pass(
+        &self,
+        _: &Scene,
+        screen: Option<&Screen>,
+        _: usize,
+        _: &mut dyn Rng,
+    ) -> Option<Box<dyn Integrator>> {
+        match screen {
+            Some(screen) if screen.eye().polarizer.is_none() => {
+                Some(Box::new(BidirectionalPass {
+                    depth: self.depth.clone(),
+                    screen: screen.clone(),
+                }))
+            }
+            _ => None,
+        }
+    }
+
+    fn trace(&self, scene: &Scene, ray: &Ray, rng: &mut dyn Rng) -> Density {
+        trace(scene, &self.depth, None, ray, rng)
+    }
+}
+
+// the pass of the screen, its pixels are found for the light subpaths reaching the eye
+struct BidirectionalPass {
+    depth: Depth,
+    screen: Screen,
+}
+
+impl Integrator for BidirectionalPass {
+    fn trace(&self, scene: &Scene, ray: &Ray, rng: &mut dyn Rng) -> Density {
+        trace(scene, &self.depth, Some(&self.screen), ray, rng)
+    }
+
+    // every band gets as many light subpaths as the screen has pixels, the same number
+    // of the rays the eye traces, so the strategies are weighted by their densities only
+    fn splat(
+        &self,
+        scene: &Scene,
+        mut rng: &mut dyn Rng,
+        splat: &mut dyn FnMut(usize, &Frequency, Density),
+    ) {
+        let (depth, screen) = (&self.depth, &self.screen);
+        let size = screen.size();
+        let paths = size.horizontal_count * size.vertical_count;
+        for frequency in Frequency::all() {
+            for _ in 0..paths {
+                let light = light_subpath(scene, depth, &frequency, &mut rng);
+                for j in 0..light.len() {
+                    let connection = connect_eye(scene, depth, screen, &light, j, &frequency);
+                    if let Some((pixel, radiance)) = connection {
+                        splat(pixel, &frequency, radiance);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// the light subpath has the frequency of the ray, the contributions of all strategies
+// building the same path are weighted by the heuristic, the strategies connecting
+// the light subpath to the eye are weighted in only when the eye of the `screen` traces the ray
+fn trace(
+    scene: &Scene,
+    depth: &Depth,
+    screen: Option<&Screen>,
+    ray: &Ray,
+    mut rng: &mut dyn Rng,
+) -> Density {
+    let frequency = ray.frequency();
+    let light = light_subpath(scene, depth, &frequency, &mut rng);
+
+    let eye = Vertex {
+        position: ray.position(),
+        normal: ray.direction(),
+        surface: None,
+    };
+    let mut eye = vec![eye];
+    let mut ray = ray.clone();
+    let mut radiance = 0.0;
+
+    // the light subpath cannot be connected after the frequency is changed
+    let mut shifted = false;
+    let mut throughput = 1.0;
+
+    while let Some(result) = scene.intersect(&ray) {
+        eye.push(Vertex {
+            position: result.position,
+            normal: result.normal.normalize(),
+            surface: Some(result.surface),
+        });
+
+        let bsdf = result.surface.bsdf();
+        let emission = bsdf.emission(&ray.frequency());
+        if emission > 0.0 {
+            let weight = if shifted {
+                1.0
+            } else {
+                weight(scene, depth, screen, &eye, 0, &frequency)
+            };
+            radiance += emission * weight * throughput;
+        }
+
+        if !shifted {
+            for j in 0..light.len() {
+                let contribution = connect(scene, depth, screen, &eye, &light, j, &frequency);
+                radiance += contribution * throughput;
+            }
+        }
+
+        match bsdf.sample(&ray, result.position, result.normal, &mut rng) {
+            Some(scattering) => {
+                let level = eye.len() - 2;
+                match depth.survive(level, throughput * scattering.weight, &mut rng) {
+                    Some(survived) => throughput = survived,
+                    None => break,
+                }
+                shifted = shifted || scattering.ray.frequency() != frequency;
+                ray = scattering.ray;
+            }
+            None => break,
+        }
+    }
+
+    radiance
+}
+
+// path of the light from the random point of the random emitter, the throughput
+// of the first vertex is the emission divided by the density of the point
+pub(crate) fn light_subpath<'a>(
+    scene: &'a Scene,
+    depth: &Depth,
+    frequency: &Frequency,
+    mut rng: &mut dyn Rng,
+) -> Vec<LightVertex<'a>> {
+    let mut path = Vec::with_capacity(depth.maximal());
+    let count = scene.emitter_count();
+    if count == 0 {
+        return path;
+    }
+
+    // either side of the emitter emits
+    let primitive = scene.emitter(Range::new(0, count).sample(&mut rng));
+    let (position, normal) = primitive.sample_point(&mut rng);
+    let normal = if Range::new(0.0, 1.0).sample(&mut rng) < 0.5 {
+        normal
+    } else {
+        normal * (-1.0)
+    };
+
+    let surface = primitive.surface();
+    let emission = surface.bsdf().emission(frequency);
+    if emission <= 0.0 {
+        return path;
+    }
+
+    let throughput = emission / scene.emitter_pdf(surface);
+    path.push(LightVertex {
+        vertex: Vertex {
+            position: position,
+            normal: normal,
+            surface: Some(surface),
+        },
+        throughput: throughput,
+    });
+
+    // the cosine of the emitted direction cancels with its density leaving pi
+    let direction = ray::cosine_direction(normal, &mut rng);
+    let mut ray = Ray::new(position + direction * Ray::EPS, direction, frequency.clone());
+    let mut throughput = throughput * M_PI;
+
+    // the roulette is played on the throughput relative to the emitted one
+    let mut relative = 1.0;
+
+    while let Some(result) = scene.intersect(&ray) {
+        path.push(LightVertex {
+            vertex: Vertex {
+                position: result.position,
+                normal: result.normal.normalize(),
+                surface: Some(result.surface),
+            },
+            throughput: throughput,
+        });
+
+        let bsdf = result.surface.bsdf();
+        match bsdf.sample(&ray, result.position, result.normal, &mut rng) {
+            Some(scattering) => {
+                if scattering.ray.frequency() != *frequency {
+                    break;
+                }
+                let factor = if scattering.singular {
+                    scattering.weight
+                } else {
+                    adjoint(bsdf, &ray, &scattering.ray, result.normal, frequency)
+                };
+                match depth.survive(path.len() - 1, relative * factor, &mut rng) {
+                    Some(survived) => {
+                        throughput *= survived / relative;
+                        relative = survived;
+                    }
+                    None => break,
+                }
+                ray = scattering.ray;
+            }
+            None => break,
+        }
+    }
+
+    path
+}
+
+// the bsdf is not symmetric, the light going along the traced ray is scattered
+// by the bsdf with the directions swapped, divided by the density of the sampled one
+fn adjoint(
+    bsdf: &dyn Bsdf,
+    ray: &Ray,
+    scattered: &Ray,
+    normal: V3,
+    frequency: &Frequency,
+) -> Density {
+    let (incident, outgoing) = (ray.direction().normalize(), scattered.direction().normalize());
+    let normal = normal.normalize();
+    let pdf = bsdf.pdf(incident, outgoing, facing(normal, incident), frequency);
+    let value = bsdf.evaluate(-outgoing, -incident, facing(normal, -outgoing), frequency);
+    let cosine = (normal * incident).abs();
+    if pdf > 0.0 && cosine > 0.0 {
+        value / cosine * (normal * outgoing).abs() / pdf
+    } else {
+        0.0
+    }
+}
+
+// weighted contribution of the connection of the last eye vertex to the `j`-th light vertex
+fn connect(
+    scene: &Scene,
+    depth: &Depth,
+    screen: Option<&Screen>,
+    eye: &[Vertex],
+    light: &[LightVertex],
+    j: usize,
+    frequency: &Frequency,
+) -> Density {
+    let (z, y) = (&eye[eye.len() - 1], &light[j]);
+    let z_surface = match z.surface {
+        Some(z_surface) => z_surface,
+        None => return 0.0,
+    };
+
+    let offset = y.vertex.position - z.position;
+    let distance2 = offset * offset;
+    let direction = offset.normalize();
+
+    let incident = (z.position - eye[eye.len() - 2].position).normalize();
+    let normal = facing(z.normal, incident);
+    let value = z_surface.bsdf().evaluate(incident, direction, normal, frequency);
+    if value <= 0.0 {
+        return 0.0;
+    }
+
+    let throughput = outgoing(light, j, direction, frequency);
+    let origin = z.position + direction * Ray::EPS;
+    if throughput <= 0.0 || !visible(scene, origin, direction, &y.vertex, frequency) {
+        return 0.0;
+    }
+
+    let mut path = eye.to_vec();
+    path.extend(light[..(j + 1)].iter().rev().map(|vertex| vertex.vertex.clone()));
+    let contribution = value * throughput * (y.vertex.normal * direction).abs() / distance2;
+
+    contribution * weight(scene, depth, screen, &path, j + 1, frequency)
+}
+
+// weighted contribution of the connection of the `j`-th light vertex to the eye
+// along with the pixel of the screen it is seen in
+fn connect_eye(
+    scene: &Scene,
+    depth: &Depth,
+    screen: &Screen,
+    light: &[LightVertex],
+    j: usize,
+    frequency: &Frequency,
+) -> Option<(usize, Density)> {
+    let (position, y) = (screen.eye().position, &light[j]);
+    let offset = y.vertex.position - position;
+    let distance2 = offset * offset;
+    let direction = offset.normalize();
+    let pixel = screen.pixel(direction)?;
+
+    let throughput = outgoing(light, j, direction, frequency);
+    if throughput <= 0.0 || !visible(scene, position, direction, &y.vertex, frequency) {
+        return None;
+    }
+
+    let eye = Vertex {
+        position: position,
+        normal: direction,
+        surface: None,
+    };
+    let mut path = vec![eye];
+    path.extend(light[..(j + 1)].iter().rev().map(|vertex| vertex.vertex.clone()));
+
+    // the density of the direction through the screen turns the radiance into the importance
+    let contribution = throughput * (y.vertex.normal * direction).abs() / distance2;
+    let importance = screen.pdf(direction);
+    let weight = weight(scene, depth, Some(screen), &path, j + 1, frequency);
+    Some((pixel, contribution * importance * weight))
+}
+
+// throughput of the light leaving the `j`-th light vertex against the `direction`
+fn outgoing(light: &[LightVertex], j: usize, direction: V3, frequency: &Frequency) -> Density {
+    let y = &light[j];
+    let surface = match y.vertex.surface {
+        Some(surface) => surface,
+        None => return 0.0,
+    };
+
+    if j == 0 {
+        // the emitter sampled side should face the direction the light leaves in
+        if y.vertex.normal * direction >= 0.0 {
+            0.0
+        } else {
+            y.throughput
+        }
+    } else {
+        let toward = (light[j - 1].vertex.position - y.vertex.position).normalize();
+        let normal = facing(y.vertex.normal, direction);
+        let value = surface.bsdf().evaluate(direction, toward, normal, frequency);
+        let cosine = (y.vertex.normal * toward).abs();
+        if value <= 0.0 || cosine <= 0.0 {
+            0.0
+        } else {
+            y.throughput * value / cosine
+        }
+    }
+}
+
+// the ray from the `origin` in the `direction` reaches the vertex first
+fn visible(
+    scene: &Scene,
+    origin: V3,
+    direction: V3,
+    vertex: &Vertex,
+    frequency: &Frequency,
+) -> bool {
+    let shadow = Ray::new(origin, direction, frequency.clone());
+    match (scene.intersect(&shadow), vertex.surface) {
+        (Some(hit), Some(surface)) => {
+            let distance = (hit.position - vertex.position).length();
+            ptr::eq(hit.surface, surface) && distance < Ray::EPS
+        }
+        _ => false,
+    }
+}
+
+// heuristic weight of the strategy taking `s` last vertices of the path from the light subpath,
+// the path starting at the eye of the `screen` might be taken from the light subpath entirely
+fn weight(
+    scene: &Scene,
+    depth: &Depth,
+    screen: Option<&Screen>,
+    path: &[Vertex],
+    s: usize,
+    frequency: &Frequency,
+) -> Density {
+    let k = path.len() - 1;
+    let direction = |a: usize, b: usize| (path[b].position - path[a].position).normalize();
+
+    // converts the density per solid angle at `a` into the density per area at `b`
+    let area = |a: usize, b: usize| {
+        let offset = path[b].position - path[a].position;
+        (path[b].normal * offset.normalize()).abs() / (offset * offset)
+    };
+
+    // density of the vertex `c` sampled at `b` which is reached from `a`
+    let scattering = |a: usize, b: usize, c: usize| match path[b].surface {
+        Some(surface) => {
+            let incident = direction(a, b);
+            let normal = facing(path[b].normal, incident);
+            let pdf = surface.bsdf().pdf(incident, direction(b, c), normal, frequency);
+            pdf * area(b, c)
+        }
+        None => 0.0,
+    };
+
+    let value = |a: usize, b: usize, c: usize| match path[b].surface {
+        Some(surface) => {
+            let incident = direction(a, b);
+            let normal = facing(path[b].normal, incident);
+            surface.bsdf().evaluate(incident, direction(b, c), normal, frequency)
+        }
+        None => 0.0,
+    };
+
+    let eye = |i: usize| scattering(i - 2, i - 1, i);
+    let light = |i: usize| {
+        if i == k {
+            path[k].surface.map(|surface| scene.emitter_pdf(surface)).unwrap_or(0.0)
+        } else if i == k - 1 {
+            (path[k].normal * direction(k, i)).abs() / M_PI * area(k, i)
+        } else {
+            scattering(i + 2, i + 1, i)
+        }
+    };
+
+    // singular scattering has no density, it is the same for all strategies then
+    let remap = |pdf: Density| if pdf > 0.0 { pdf } else { 1.0 };
+
+    // density of the first vertex found from the eye, it is the same for all strategies
+    // tracing the ray from the eye, so it matters only against the ones connecting to the eye
+    let first = |s: usize| match screen {
+        Some(screen) if s < k => screen.pdf(direction(0, 1)) * area(0, 1),
+        _ => 1.0,
+    };
+
+    let valid = |s: usize| {
+        let z = k - s;
+        if z > depth.maximal() || s > depth.maximal() {
+            false
+        } else if s == k {
+            screen.is_some() && (k == 1 || value(0, 1, 2) > 0.0)
+        } else if s == 0 {
+            true
+        } else {
+            value(z - 1, z, z + 1) > 0.0 && (s == 1 || value(z, z + 1, z + 2) > 0.0)
+        }
+    };
+
+    let probability = |s: usize| {
+        let eye = (2..(k - s + 1)).fold(first(s), |product, i| product * remap(eye(i)));
+        ((k - s + 1)..(k + 1)).fold(eye, |product, i| product * remap(light(i)))
+    };
+
+    let heuristic = scene.heuristic();
+    let total = (0..(k + 1))
+        .filter(|&s| valid(s))
+        .fold(0.0, |sum, s| sum + heuristic.power(probability(s)));
+    let own = heuristic.power(probability(s));
+    if total > 0.0 {
+        own / total
+    } else {
+        1.0
+    }
+}
+
+// the normal turned toward the incident ray
+pub(crate) fn facing(normal: V3, incident: V3) -> V3 {
+    if normal * incident > 0.0 {
+        normal * (-1.0)
+    } else {
+        normal
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use super::super::fixture;
+    use super::super::algebra::M;
+    use super::super::beam::Beam;
+    use super::super::beam::BeamRefract;
+    use super::super::beam::Material;
+    use super::super::primitive::Sphere;
+    use super::super::primitive::Triangle;
+    use super::super::scene::Method;
+    use super::super::integrator::PathTracing;
+    use super::super::screen::Eye;
+    use super::super::screen::Size;
+
+    use rand;
+
+    #[test]
+    fn unbiased() {
+        // the same answer as the path tracing gives for the floor under the lamp
+        let mut rng = rand::thread_rng();
+        let scene = fixture::lamp(Method::Bidirectional(Bidirectional::default()));
+
+        let samples = 20000;
+        let ray = fixture::ray();
+        let sum = (0..samples).fold(0.0, |sum, _| sum + scene.trace(&ray, &mut rng));
+        assert!((sum / (samples as Density) - fixture::expected()).abs() < 0.01);
+    }
+
+    #[test]
+    fn indirect() {
+        // the floor lit by the wall only matches the path tracing
+        let mut rng = rand::thread_rng();
+        let scene = |method: Method| {
+            let lamp = Material::emission(Beam::flat(1.0));
+            let lamp = Sphere::new(V3::new(-2.0, 0.0, 1.0), 0.5, lamp);
+            let blind = Sphere::new(V3::new(-1.0, 0.0, 0.5), 0.45, Material::default());
+            let floor = fixture::floor(Material::diffuse(Beam::flat(0.8)));
+            let wall = Triangle::new(
+                V3::new(-3.0, -100.0, -1.0),
+                V3::new(-3.0, 100.0, -1.0),
+                V3::new(-3.0, 0.0, 100.0),
+                Material::diffuse(Beam::flat(0.8)),
+            );
+            Scene::new(vec![lamp, blind], vec![floor, wall]).with_method(method)
+        };
+
+        let samples = 200000;
+        let direction = V3::new(-1.0, 0.0, -1.0).normalize();
+        let ray = Ray::new(V3::new(1.0, 0.0, 1.0), direction, Frequency::new(0));
+        let mean = |scene: Scene, rng: &mut dyn Rng| {
+            let sum = (0..samples).fold(0.0, |sum, _| sum + scene.trace(&ray, rng));
+            sum / (samples as Density)
+        };
+
+        let path = mean(scene(Method::PathTracing(PathTracing::default())), &mut rng);
+        let bidirectional = Method::Bidirectional(Bidirectional::default());
+        let bidirectional = mean(scene(bidirectional), &mut rng);
+        assert!((path - bidirectional).abs() < 0.2 * path);
+    }
+
+    #[test]
+    fn caustic() {
+        // the small lamp lights the floor through the glass slab, the shadow rays hit the glass,
+        // so the path tracing finds the lamp only when the diffuse bounce goes toward it
+        let mut rng = rand::thread_rng();
+        let scene = |method: Method| {
+            let lamp = Material::emission(Beam::flat(100.0));
+            let lamp = Sphere::new(V3::new(0.0, 0.0, 2.0), 0.1, lamp);
+            let glass = BeamRefract::from_band_index(|_| 1.5);
+            let glass = Material::refraction(Beam::flat(1.0), glass);
+            let plane = |z: M, material: Material| {
+                Triangle::new(
+                    V3::new(-100.0, -100.0, z),
+                    V3::new(100.0, -100.0, z),
+                    V3::new(0.0, 100.0, z),
+                    material,
+                )
+            };
+            let floor = plane(0.0, Material::diffuse(Beam::flat(0.8)));
+            let slab = vec![floor, plane(1.0, glass.clone()), plane(1.1, glass)];
+            Scene::new(vec![lamp], slab).with_method(method)
+        };
+
+        let forward = V3::new(0.0, 3.0, -0.5).normalize();
+        let right = V3::new(1.0, 0.0, 0.0);
+        let eye = Eye {
+            position: V3::new(0.0, -3.0, 0.5),
+            forward: forward,
+            right: right,
+            up: right.cross(forward),
+            width: 0.6,
+            height: 0.3,
+            distance: 1.0,
+            polarizer: None,
+        };
+        let format = Size {
+            horizontal_count: 4,
+            vertical_count: 4,
+        };
+        let screen = Screen::new(format, eye);
+
+        // luminance of every pass
+        let passes = 300;
+        let luminance = |scene: &Scene, rng: &mut dyn Rng| {
+            (0..passes)
+                .map(|_| {
+                    let mut image = screen.create_image();
+                    screen.sample(scene, &mut image, rng);
+                    image.channels().iter().fold(0.0, |sum, pixel| sum + pixel[1])
+                })
+                .collect::<Vec<_>>()
+        };
+        let statistics = |values: Vec<Density>| {
+            let n = values.len() as Density;
+            let mean = values.iter().fold(0.0, |sum, value| sum + value) / n;
+            let square = values.iter().fold(0.0, |sum, value| sum + value * value) / n;
+            (mean, (square - mean * mean).max(0.0).sqrt())
+        };
+
+        let (path, path_deviation) = statistics(luminance(&scene(Method::default()), &mut rng));
+        let bidirectional = Method::Bidirectional(Bidirectional::default());
+        let (bidirectional, deviation) = statistics(luminance(&scene(bidirectional), &mut rng));
+        let error = path_deviation / (passes as Density).sqrt();
+        assert!((path - bidirectional).abs() < 4.0 * error);
+        assert!(deviation * 2.0 < path_deviation);
+    }
+}
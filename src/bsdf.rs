@@ -0,0 +1,258 @@
+use super::algebra::V3;
+use super::algebra::M_PI;
+
+use super::beam::Material;
+use super::beam::Frequency;
+use super::beam::Density;
+use super::beam::SingleFate;
+
+use super::ray::Ray;
+use super::ray::PhotonicRay;
+use super::ray::GeometricalRay;
+use super::ray::PolarizedRay;
+
+use super::polarization::Mueller;
+
+use std::sync::Arc;
+
+use rand::Rng;
+use rand::distributions::Sample;
+use rand::distributions::Range;
+
+/// Bsdf trait describes how a surface scatters the light,
+/// rays are traced backward from the eye, so the `incident` is the direction
+/// of the traced ray toward the surface and the `outgoing` is the direction it continues,
+/// the `normal` always faces the incident ray and might be not normalized
+pub trait Bsdf: Send + Sync {
+    /// radiance the surface emits toward the ray hitting it
+    fn emission(&self, frequency: &Frequency) -> Density;
+
+    /// continue the ray, the ray might change its frequency and polarization here
+    fn sample(&self, ray: &Ray, position: V3, normal: V3, rng: &mut dyn Rng) -> Option<Scattering>;
+
+    /// bsdf multiplied by the cosine between the `outgoing` and the `normal`,
+    /// singular components like mirror reflection are not included
+    fn evaluate(&self, incident: V3, outgoing: V3, normal: V3, frequency: &Frequency) -> Density;
+
+    /// probability density of choosing the `outgoing` direction in `sample`
+    fn pdf(&self, incident: V3, outgoing: V3, normal: V3, frequency: &Frequency) -> Density;
+}
+
+/// Scattering struct is the ray continued by `Bsdf::sample`
+pub struct Scattering {
+    pub ray: Ray,
+    /// value of `evaluate` divided by `pdf` for the chosen direction
+    pub weight: Density,
+    /// the direction is chosen from the component not included in `evaluate`,
+    /// so the light cannot be sampled explicitly there
+    pub singular: bool,
+}
+
+/// Surface enum is what primitives are made of
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Surface {
+    Material(Box<Material>),
+    /// user defined scattering model, the scene containing it cannot be serialized
+    #[serde(skip_serializing, skip_deserializing)]
+    Custom(Arc<dyn Bsdf>),
+}
+
+impl Surface {
+    pub fn custom<B>(bsdf: B) -> Self
+    where
+        B: Bsdf + 'static,
+    {
+        Surface::Custom(Arc::new(bsdf))
+    }
+
+    pub fn bsdf(&self) -> &dyn Bsdf {
+        match *self {
+            Surface::Material(ref material) => material.as_ref(),
+            Surface::Custom(ref bsdf) => bsdf.as_ref(),
+        }
+    }
+}
+
+impl From<Material> for Surface {
+    fn from(material: Material) -> Self {
+        Surface::Material(Box::new(material))
+    }
+}
+
+impl Bsdf for Material {
+    fn emission(&self, frequency: &Frequency) -> Density {
+        self.emission_density(frequency)
+    }
+
+    fn sample(
+        &self,
+        ray: &Ray,
+        position: V3,
+        normal: V3,
+        mut rng: &mut dyn Rng,
+    ) -> Option<Scattering> {
+        // the normal of the triangle is scaled by its area
+        let normal = normal.normalize();
+        let fate = self.fate(&ray.frequency(), &mut rng);
+        let singular = !matches!(fate.single, SingleFate::Diffuse);
+        let mut weight = fate.weight;
+
+        use self::SingleFate::*;
+        let new_ray = match fate.single {
+            Decay => None,
+            Diffuse => Some(ray.diffuse(position, normal, &mut rng)),
+            Reflect => Some(ray.reflect(position, normal)),
+            // the thin sheet of the identity factor doesn't bound a medium,
+            // the ray passes straight through and stays on its side
+            Refract(1.0) => Some(ray.redirect(position, ray.direction())),
+            Refract(factor) => {
+                // the ray is split by the Fresnel equations, both parts are polarized,
+                // the ray leaving the medium is refracted by the inverse factor
+                let factor = if ray.inside() { 1.0 / factor } else { factor };
+                let cosine = (ray.direction() * normal).abs();
+                let ray = ray.align(ray.direction().cross(normal));
+                let reflection = Mueller::fresnel_reflection(cosine, factor);
+                let (reflected, reflectance) = ray.filter(&reflection);
+                if Range::new(0.0, 1.0).sample(&mut rng) < reflectance {
+                    Some(reflected.reflect(position, normal))
+                } else {
+                    let transmission = Mueller::fresnel_transmission(cosine, factor);
+                    let (refracted, _) = ray.filter(&transmission);
+                    Some(refracted.refract(position, normal, factor))
+                }
+            }
+            Fluoresce(excitation) => Some(ray.diffuse(position, normal, &mut rng).shift(excitation)),
+            Interfere(film) => {
+                let cosine = (ray.direction() * normal).abs();
+                let inside = ray.inside();
+                let reflectance = film.reflectance(ray.frequency().wavelength(), cosine, inside);
+                if Range::new(0.0, 1.0).sample(&mut rng) < reflectance {
+                    Some(ray.reflect(position, normal))
+                } else {
+                    Some(ray.refract(position, normal, film.factor(inside)))
+                }
+            }
+            Diffract(grating) => {
+                let wavelength = ray.frequency().wavelength();
+                match grating.diffract(ray.direction(), normal, wavelength, &mut rng) {
+                    Some((direction, efficiency)) => {
+                        weight *= efficiency;
+                        Some(ray.redirect(position, direction))
+                    }
+                    None => None,
+                }
+            }
+        };
+
+        // the filter attenuates the ray instead of absorbing it randomly
+        let (new_ray, weight) = match (new_ray, fate.filter) {
+            (Some(new_ray), Some(filter)) => {
+                let new_ray = new_ray.align(filter.axis());
+                let (new_ray, transmittance) = new_ray.filter(filter.mueller());
+                (Some(new_ray), weight * transmittance)
+            }
+            (new_ray, _) => (new_ray, weight),
+        };
+
+        new_ray.map(|new_ray| {
+            Scattering {
+                ray: new_ray,
+                weight: weight,
+                singular: singular,
+            }
+        })
+    }
+
+    fn evaluate(&self, _: V3, outgoing: V3, normal: V3, frequency: &Frequency) -> Density {
+        // diffuse rays are spread uniformly over the hemisphere
+        if outgoing * normal > 0.0 {
+            self.diffuse_density(frequency) / (2.0 * M_PI)
+        } else {
+            0.0
+        }
+    }
+
+    fn pdf(&self, incident: V3, outgoing: V3, normal: V3, frequency: &Frequency) -> Density {
+        // the diffuse fate is chosen among the surviving ones proportionally to its density
+        let survival = self.survival(frequency);
+        if survival > 0.0 {
+            self.evaluate(incident, outgoing, normal, frequency) / survival
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use super::super::beam::Beam;
+    use super::super::scene::Scene;
+    use super::super::primitive::Sphere;
+
+    use rand;
+
+    struct Lamp;
+
+    impl Bsdf for Lamp {
+        fn emission(&self, _: &Frequency) -> Density {
+            1.0
+        }
+
+        fn sample(&self, _: &Ray, _: V3, _: V3, _: &mut dyn Rng) -> Option<Scattering> {
+            None
+        }
+
+        fn evaluate(&self, _: V3, _: V3, _: V3, _: &Frequency) -> Density {
+            0.0
+        }
+
+        fn pdf(&self, _: V3, _: V3, _: V3, _: &Frequency) -> Density {
+            0.0
+        }
+    }
+
+    #[test]
+    fn custom() {
+        let mut rng = rand::thread_rng();
+        let lamp = Sphere::new(V3::new(0.0, 0.0, 10.0), 1.0, Surface::custom(Lamp));
+        let scene = Scene::new(vec![lamp], vec![]);
+
+        let ray = Ray::new(V3::new(0.0, 0.0, 0.0), V3::new(0.0, 0.0, 1.0), Frequency::new(0));
+        assert_eq!(scene.trace(&ray, &mut rng), 1.0);
+
+        let ray = Ray::new(V3::new(0.0, 0.0, 0.0), V3::new(0.0, 1.0, 0.0), Frequency::new(0));
+        assert_eq!(scene.trace(&ray, &mut rng), 0.0);
+    }
+
+    #[test]
+    fn weighted() {
+        // the gray surface scatters every ray with half of the throughput
+        let mut rng = rand::thread_rng();
+        let gray = Material::diffuse(Beam::flat(0.3)) + Material::reflection(Beam::flat(0.2));
+        let ray = Ray::new(V3::new(0.0, 0.0, 1.0), V3::new(0.0, 0.0, -1.0), Frequency::new(0));
+        let normal = V3::new(0.0, 0.0, 1.0);
+        for _ in 0..100 {
+            let scattering = gray.sample(&ray, V3::new(0.0, 0.0, 0.0), normal, &mut rng).unwrap();
+            assert!((scattering.weight - 0.5).abs() < 1.0e-9);
+        }
+
+        let outgoing = V3::new(0.0, 0.6, 0.8);
+        let frequency = Frequency::new(0);
+        let value = gray.evaluate(-normal, outgoing, normal, &frequency);
+        let pdf = gray.pdf(-normal, outgoing, normal, &frequency);
+        assert!((value / pdf - 0.5).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn mirror() {
+        // the mirror reflects the ray along the unit direction whatever the normal length
+        let mut rng = rand::thread_rng();
+        let mirror = Material::reflection(Beam::flat(1.0));
+        let ray = Ray::new(V3::new(0.0, 0.0, 1.0), V3::new(0.6, 0.0, -0.8), Frequency::new(0));
+        let normal = V3::new(0.0, 0.0, 40000.0);
+        let scattering = mirror.sample(&ray, V3::new(0.0, 0.0, 0.0), normal, &mut rng).unwrap();
+        assert!((scattering.ray.direction() - V3::new(0.6, 0.0, 0.8)).length() < 1.0e-9);
+    }
+}
@@ -1,7 +1,13 @@
 use super::beam::RGB;
 
+use super::algebra::M;
+
 pub const TABLE_SIZE: usize = 48;
 
+pub const CIE_SIZE: usize = 41;
+pub const CIE_START: M = 380.0;
+pub const CIE_STEP: M = 10.0;
+
 #[inline(always)]
 pub fn table() -> [(f32, RGB); TABLE_SIZE] {
     [
@@ -55,3 +61,133 @@ pub fn table() -> [(f32, RGB); TABLE_SIZE] {
         (727.27, RGB::new( 3.8539E-03, -3.2577E-05,  4.5185E-06)),
     ]
 }
+
+/// color matching functions at the given wavelength in nanometres,
+/// linearly interpolated in frequency between entries of the table
+pub fn interpolate(wavelength: M) -> RGB {
+    let frequency = 1.0 / wavelength;
+    let table = table();
+    for pair in table.windows(2) {
+        let (higher, lower) = (1.0 / (pair[0].0 as M), 1.0 / (pair[1].0 as M));
+        if frequency <= higher && frequency >= lower {
+            let t = (higher - frequency) / (higher - lower);
+            return pair[0].1.clone() * (1.0 - t) + pair[1].1.clone() * t;
+        }
+    }
+
+    RGB::default()
+}
+
+/// CIE 1931 2-degree color matching functions from 380 to 780 nanometres
+#[inline(always)]
+pub fn cie_1931() -> [(M, M, M); CIE_SIZE] {
+    [
+        (0.001368, 0.000039, 0.006450),
+        (0.004243, 0.000120, 0.020050),
+        (0.014310, 0.000396, 0.067850),
+        (0.043510, 0.001210, 0.207400),
+        (0.134380, 0.004000, 0.645600),
+        (0.283900, 0.011600, 1.385600),
+        (0.348280, 0.023000, 1.747060),
+        (0.336200, 0.038000, 1.772110),
+        (0.290800, 0.060000, 1.669200),
+        (0.195360, 0.090980, 1.287640),
+        (0.095640, 0.139020, 0.812950),
+        (0.032010, 0.208020, 0.465180),
+        (0.004900, 0.323000, 0.272000),
+        (0.009300, 0.503000, 0.158200),
+        (0.063270, 0.710000, 0.078250),
+        (0.165500, 0.862000, 0.042160),
+        (0.290400, 0.954000, 0.020300),
+        (0.433450, 0.994950, 0.008750),
+        (0.594500, 0.995000, 0.003900),
+        (0.762100, 0.952000, 0.002100),
+        (0.916300, 0.870000, 0.001650),
+        (1.026300, 0.757000, 0.001100),
+        (1.062200, 0.631000, 0.000800),
+        (1.002600, 0.503000, 0.000340),
+        (0.854450, 0.381000, 0.000190),
+        (0.642400, 0.265000, 0.000050),
+        (0.447900, 0.175000, 0.000020),
+        (0.283500, 0.107000, 0.000000),
+        (0.164900, 0.061000, 0.000000),
+        (0.087400, 0.032000, 0.000000),
+        (0.046770, 0.017000, 0.000000),
+        (0.022700, 0.008210, 0.000000),
+        (0.011359, 0.004102, 0.000000),
+        (0.005790, 0.002091, 0.000000),
+        (0.002899, 0.001047, 0.000000),
+        (0.001440, 0.000520, 0.000000),
+        (0.000690, 0.000249, 0.000000),
+        (0.000332, 0.000120, 0.000000),
+        (0.000166, 0.000060, 0.000000),
+        (0.000083, 0.000030, 0.000000),
+        (0.000042, 0.000015, 0.000000),
+    ]
+}
+
+/// CIE 1964 10-degree color matching functions from 380 to 780 nanometres
+#[inline(always)]
+pub fn cie_1964() -> [(M, M, M); CIE_SIZE] {
+    [
+        (0.000160, 0.000017, 0.000705),
+        (0.002362, 0.000253, 0.010482),
+        (0.019110, 0.002004, 0.086011),
+        (0.084736, 0.008756, 0.389366),
+        (0.204492, 0.021391, 0.972542),
+        (0.314679, 0.038676, 1.553480),
+        (0.383734, 0.062077, 1.967280),
+        (0.370702, 0.089456, 1.994800),
+        (0.302273, 0.128201, 1.745370),
+        (0.195618, 0.185190, 1.317560),
+        (0.080507, 0.253589, 0.772125),
+        (0.016172, 0.339133, 0.415254),
+        (0.003816, 0.460777, 0.218502),
+        (0.037465, 0.606741, 0.112044),
+        (0.117749, 0.761757, 0.060709),
+        (0.236491, 0.875211, 0.030451),
+        (0.376772, 0.961988, 0.013676),
+        (0.529826, 0.991761, 0.003988),
+        (0.705224, 0.997340, 0.000000),
+        (0.878655, 0.955552, 0.000000),
+        (1.014160, 0.868934, 0.000000),
+        (1.118520, 0.777405, 0.000000),
+        (1.123990, 0.658341, 0.000000),
+        (1.030480, 0.527963, 0.000000),
+        (0.856297, 0.398057, 0.000000),
+        (0.647467, 0.283493, 0.000000),
+        (0.431567, 0.179828, 0.000000),
+        (0.268329, 0.107633, 0.000000),
+        (0.152568, 0.060281, 0.000000),
+        (0.081261, 0.031800, 0.000000),
+        (0.040851, 0.015905, 0.000000),
+        (0.019941, 0.007749, 0.000000),
+        (0.009577, 0.003718, 0.000000),
+        (0.004553, 0.001768, 0.000000),
+        (0.002175, 0.000846, 0.000000),
+        (0.001045, 0.000407, 0.000000),
+        (0.000508, 0.000199, 0.000000),
+        (0.000251, 0.000098, 0.000000),
+        (0.000126, 0.000050, 0.000000),
+        (0.000065, 0.000025, 0.000000),
+        (0.000033, 0.000013, 0.000000),
+    ]
+}
+
+/// color matching functions from the CIE table at the given wavelength in nanometres
+pub fn matching(table: &[(M, M, M); CIE_SIZE], wavelength: M) -> (M, M, M) {
+    let position = (wavelength - CIE_START) / CIE_STEP;
+    if position < 0.0 || position > (CIE_SIZE - 1) as M {
+        (0.0, 0.0, 0.0)
+    } else {
+        let i = (position as usize).min(CIE_SIZE - 2);
+        let t = position - i as M;
+        let (a, b) = (table[i], table[i + 1]);
+        (a.0 * (1.0 - t) + b.0 * t, a.1 * (1.0 - t) + b.1 * t, a.2 * (1.0 - t) + b.2 * t)
+    }
+}
+
+/// photopic luminous efficiency at the given wavelength in nanometres
+pub fn luminosity(wavelength: M) -> M {
+    matching(&cie_1931(), wavelength).1
+}
@@ -0,0 +1,319 @@
+use std::ops::Add;
+use std::ops::Mul;
+use std::ops::Div;
+
+use super::algebra::V3;
+use super::algebra::M;
+
+use super::beam::Beam;
+use super::beam::RGB;
+use super::beam::Density;
+
+use super::color;
+
+/// Observer enum is a set of color matching functions
+#[derive(Default, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Observer {
+    /// 2-degree standard observer
+    #[default]
+    Cie1931,
+    /// 10-degree standard observer
+    Cie1964,
+}
+
+impl Observer {
+    /// color matching functions integrated over every band, normalized so
+    /// the flat spectrum of density one has `Y` equal to one, unless it is invisible
+    pub fn matching(&self) -> (Beam, Beam, Beam) {
+        let table = match *self {
+            Observer::Cie1931 => color::cie_1931(),
+            Observer::Cie1964 => color::cie_1964(),
+        };
+
+        // band averages weighted by the band widths
+        let function = |k: usize| {
+            let average = Beam::from_function(|wavelength| {
+                let xyz = color::matching(&table, wavelength);
+                [xyz.0, xyz.1, xyz.2][k]
+            });
+            Beam::from_bands(|frequency| {
+                let (shortest, longest) = frequency.bounds();
+                average.density(frequency) * (longest - shortest)
+            })
+        };
+
+        // the spectrum the observer doesn't see leaves the functions zero
+        let (x, y, z) = (function(0), function(1), function(2));
+        let total = y.clone() * &Beam::flat(1.0);
+        let scale = if total > 0.0 { 1.0 / total } else { 0.0 };
+        (x * scale, y * scale, z * scale)
+    }
+}
+
+/// ColorSpace enum is a space the image is converted to at output,
+/// D65 white of the image is the white of every space
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    DisplayP3,
+    Rec2020,
+    /// linear space with the primaries AP1 and D60 white point,
+    /// D65 is Bradford adapted to D60 before the conversion
+    AcesCg,
+}
+
+impl ColorSpace {
+    /// rows of the matrix converting XYZ to linear components
+    fn matrix(&self) -> [V3; 3] {
+        match *self {
+            ColorSpace::Srgb => [
+                V3::new(3.2404542, -1.5371385, -0.4985314),
+                V3::new(-0.9692660, 1.8760108, 0.0415560),
+                V3::new(0.0556434, -0.2040259, 1.0572252),
+            ],
+            ColorSpace::DisplayP3 => [
+                V3::new(2.4934969, -0.9313836, -0.4027108),
+                V3::new(-0.8294890, 1.7626641, 0.0236247),
+                V3::new(0.0358458, -0.0761724, 0.9568845),
+            ],
+            ColorSpace::Rec2020 => [
+                V3::new(1.7166512, -0.3556708, -0.2533663),
+                V3::new(-0.6666844, 1.6164812, 0.0157685),
+                V3::new(0.0176399, -0.0427706, 0.9421031),
+            ],
+            ColorSpace::AcesCg => [
+                V3::new(1.6605206, -0.3153298, -0.2414933),
+                V3::new(-0.6599409, 1.6084200, 0.0172974),
+                V3::new(0.0090095, -0.0035788, 0.9138393),
+            ],
+        }
+    }
+
+    /// linear components
+    pub fn linear(&self, xyz: &XYZ) -> RGB {
+        let matrix = self.matrix();
+        let v = xyz.vector();
+        RGB::new(matrix[0] * v, matrix[1] * v, matrix[2] * v)
+    }
+
+    /// components encoded with the transfer function of the space
+    pub fn encode(&self, xyz: &XYZ) -> RGB {
+        let linear = self.linear(xyz);
+        match *self {
+            ColorSpace::Srgb | ColorSpace::DisplayP3 => linear.map(|c| {
+                if c <= 0.0031308 {
+                    c * 12.92
+                } else {
+                    1.055 * c.powf(1.0 / 2.4) - 0.055
+                }
+            }),
+            ColorSpace::Rec2020 => linear.map(|c| {
+                if c < 0.018053968510807 {
+                    c * 4.5
+                } else {
+                    1.09929682680944 * c.powf(0.45) - 0.09929682680944
+                }
+            }),
+            ColorSpace::AcesCg => linear,
+        }
+    }
+}
+
+/// Adaptation enum is a cone response space where the white balance scales components
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub enum Adaptation {
+    #[default]
+    Bradford,
+    Cat02,
+}
+
+impl Adaptation {
+    fn matrix(&self) -> [V3; 3] {
+        match *self {
+            Adaptation::Bradford => [
+                V3::new(0.8951, 0.2664, -0.1614),
+                V3::new(-0.7502, 1.7135, 0.0367),
+                V3::new(0.0389, -0.0685, 1.0296),
+            ],
+            Adaptation::Cat02 => [
+                V3::new(0.7328, 0.4296, -0.1624),
+                V3::new(-0.7036, 1.6975, 0.0061),
+                V3::new(0.0030, 0.0136, 0.9834),
+            ],
+        }
+    }
+}
+
+/// WhiteBalance struct is a von Kries transform making the white of the scene illuminant
+/// look like the D65 white of the output color spaces
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WhiteBalance {
+    rows: [V3; 3],
+}
+
+impl WhiteBalance {
+    /// balance for the scene lit by the `illuminant` seen by the `observer`
+    pub fn new(illuminant: &Beam, observer: &Observer, adaptation: Adaptation) -> Self {
+        let matching = observer.matching();
+        let source = XYZ::from_beam(&matching, illuminant).vector();
+        let destination = XYZ::from_beam(&matching, &Beam::d65()).vector();
+        let (source, destination) = (source / source.y(), destination / destination.y());
+
+        let cone = adaptation.matrix();
+        let apply = |rows: &[V3; 3], v: V3| V3::new(rows[0] * v, rows[1] * v, rows[2] * v);
+        let (s, d) = (apply(&cone, source), apply(&cone, destination));
+        let scale = V3::new(d.x() / s.x(), d.y() / s.y(), d.z() / s.z());
+
+        // rows of the inverse are the transposed adjugate divided by the determinant
+        let determinant = cone[0].cross(cone[1]) * cone[2];
+        let (a, b, c) = V3::adj(cone[0], cone[1], cone[2]);
+        let (a, b, c) = V3::transpose(a, b, c);
+        let inverse = [a / determinant, b / determinant, c / determinant];
+
+        // inverse * diag(scale) * cone
+        let scaled = [cone[0] * scale.x(), cone[1] * scale.y(), cone[2] * scale.z()];
+        let (x, y, z) = V3::transpose(scaled[0], scaled[1], scaled[2]);
+        let rows = [
+            V3::new(inverse[0] * x, inverse[0] * y, inverse[0] * z),
+            V3::new(inverse[1] * x, inverse[1] * y, inverse[1] * z),
+            V3::new(inverse[2] * x, inverse[2] * y, inverse[2] * z),
+        ];
+
+        WhiteBalance { rows: rows }
+    }
+
+    /// balance for the light of the correlated color temperature in kelvins,
+    /// it is daylight from 4000 K and blackbody below
+    pub fn temperature(temperature: M, observer: &Observer, adaptation: Adaptation) -> Self {
+        let illuminant = if temperature < 4000.0 {
+            Beam::blackbody(temperature)
+        } else {
+            Beam::daylight(temperature)
+        };
+        Self::new(&illuminant, observer, adaptation)
+    }
+
+    pub fn apply(&self, xyz: &XYZ) -> XYZ {
+        let v = xyz.vector();
+        XYZ::new(self.rows[0] * v, self.rows[1] * v, self.rows[2] * v)
+    }
+}
+
+/// XYZ struct is a tristimulus value
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct XYZ {
+    x: Density,
+    y: Density,
+    z: Density,
+}
+
+impl XYZ {
+    pub fn new(x: Density, y: Density, z: Density) -> Self {
+        XYZ { x: x, y: y, z: z }
+    }
+
+    /// project the spectrum on the color matching functions given by `Observer::matching`
+    pub fn from_beam(matching: &(Beam, Beam, Beam), beam: &Beam) -> Self {
+        let (ref x, ref y, ref z) = *matching;
+        XYZ::new(beam.clone() * x, beam.clone() * y, beam.clone() * z)
+    }
+
+    fn vector(&self) -> V3 {
+        V3::new(self.x, self.y, self.z)
+    }
+
+    pub fn x(&self) -> Density {
+        self.x
+    }
+
+    pub fn y(&self) -> Density {
+        self.y
+    }
+
+    pub fn z(&self) -> Density {
+        self.z
+    }
+}
+
+impl Add for XYZ {
+    type Output = Self;
+
+    fn add(self, rhs: XYZ) -> Self::Output {
+        XYZ {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+        }
+    }
+}
+
+impl Mul<Density> for XYZ {
+    type Output = Self;
+
+    fn mul(self, rhs: Density) -> Self::Output {
+        XYZ {
+            x: self.x * rhs,
+            y: self.y * rhs,
+            z: self.z * rhs,
+        }
+    }
+}
+
+impl Div<usize> for XYZ {
+    type Output = Self;
+
+    fn div(self, rhs: usize) -> Self::Output {
+        let f = rhs as Density;
+        XYZ {
+            x: self.x / f,
+            y: self.y / f,
+            z: self.z / f,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn white() {
+        // D65 is the white of every space
+        let matching = Observer::Cie1931.matching();
+        let d65 = XYZ::from_beam(&matching, &Beam::d65());
+        let d65 = d65.clone() * (1.0 / d65.y());
+        let spaces = [
+            ColorSpace::Srgb,
+            ColorSpace::DisplayP3,
+            ColorSpace::Rec2020,
+            ColorSpace::AcesCg,
+        ];
+        for space in spaces.iter() {
+            let (r, g, b) = space.linear(&d65).components();
+            assert!((r - 1.0).abs() < 0.02 && (g - 1.0).abs() < 0.02 && (b - 1.0).abs() < 0.02);
+        }
+
+        let flat = XYZ::from_beam(&Observer::Cie1964.matching(), &Beam::flat(1.0));
+        assert!((flat.y() - 1.0).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn white_balance() {
+        let observer = Observer::Cie1931;
+        let matching = observer.matching();
+        for adaptation in [Adaptation::Bradford, Adaptation::Cat02].iter() {
+            // the illuminant becomes the white of the output
+            let balance = WhiteBalance::new(&Beam::a(), &observer, adaptation.clone());
+            let white = balance.apply(&XYZ::from_beam(&matching, &Beam::a()));
+            let white = white.clone() * (1.0 / white.y());
+            let (r, g, b) = ColorSpace::Srgb.linear(&white).components();
+            assert!((r - g).abs() < 0.02 && (g - b).abs() < 0.02);
+
+            // D65 needs no balance
+            let balance = WhiteBalance::temperature(6504.0, &observer, adaptation.clone());
+            let xyz = balance.apply(&XYZ::new(0.2, 0.3, 0.4));
+            assert!((xyz.x() - 0.2).abs() < 1.0e-9 && (xyz.z() - 0.4).abs() < 1.0e-9);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use super::algebra::M;
+
+use super::beam::BeamRefract;
+
+/// Dispersion models, wavelength in the equations is in micrometres as usual for glass catalogues
+impl BeamRefract {
+    /// Cauchy equation `n = A + B / l^2 + C / l^4 + ...`, the `coefficients` are `A, B, C, ...`
+    pub fn cauchy(coefficients: &[M]) -> Self {
+        Self::from_index(|wavelength| {
+            let l2 = (wavelength / 1000.0).powi(2);
+            coefficients
+                .iter()
+                .rev()
+                .fold(0.0, |index, coefficient| index / l2 + coefficient)
+        })
+    }
+
+    /// Sellmeier equation `n^2 = 1 + B * l^2 / (l^2 - C) + ...`, the `terms` are pairs `(B, C)`
+    pub fn sellmeier(terms: &[(M, M)]) -> Self {
+        Self::from_index(|wavelength| {
+            let l2 = (wavelength / 1000.0).powi(2);
+            terms
+                .iter()
+                .fold(1.0, |n2, &(b, c)| n2 + b * l2 / (l2 - c))
+                .sqrt()
+        })
+    }
+
+    /// borosilicate crown glass
+    pub fn bk7() -> Self {
+        Self::sellmeier(&[
+            (1.03961212, 0.00600069867),
+            (0.231792344, 0.0200179144),
+            (1.01046945, 103.560653),
+        ])
+    }
+
+    /// dense flint glass
+    pub fn sf11() -> Self {
+        Self::sellmeier(&[
+            (1.73759695, 0.013188707),
+            (0.313747346, 0.0623068142),
+            (1.89878101, 155.23629),
+        ])
+    }
+
+    pub fn fused_silica() -> Self {
+        Self::sellmeier(&[
+            (0.6961663, 0.0046791482),
+            (0.4079426, 0.0135120631),
+            (0.8974794, 97.9340025),
+        ])
+    }
+
+    /// water at the room temperature
+    pub fn water() -> Self {
+        Self::cauchy(&[1.3238, 0.00314])
+    }
+
+    pub fn diamond() -> Self {
+        Self::sellmeier(&[(0.3306, 0.030625), (4.3356, 0.011236)])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use super::super::beam::Frequency;
+
+    fn index(glass: &BeamRefract, wavelength: M) -> M {
+        1.0 / glass.factor(&Frequency::from_wavelength(wavelength).unwrap())
+    }
+
+    #[test]
+    fn catalogue() {
+        // the band nearest to the sodium line, so indices are a bit off
+        let cases = [
+            (BeamRefract::bk7(), 1.517),
+            (BeamRefract::sf11(), 1.785),
+            (BeamRefract::fused_silica(), 1.458),
+            (BeamRefract::water(), 1.333),
+            (BeamRefract::diamond(), 2.417),
+        ];
+
+        for &(ref glass, expected) in cases.iter() {
+            assert!((index(glass, 589.3) - expected).abs() < 0.005);
+            assert!(index(glass, 450.0) > index(glass, 650.0));
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use super::algebra::M;
+use super::algebra::M_PI;
+
+use super::beam::Density;
+use super::beam::Factor;
+
+/// ThinFilm struct is a non-absorbing layer of given thickness (in nanometres)
+/// and refraction index laying on a substrate
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ThinFilm {
+    thickness: M,
+    index: Factor,
+    substrate: Factor,
+}
+
+impl ThinFilm {
+    pub fn new(thickness: M, index: Factor, substrate: Factor) -> Self {
+        ThinFilm {
+            thickness: thickness,
+            index: index,
+            substrate: substrate,
+        }
+    }
+
+    /// film surrounded by air on both sides, like a soap bubble
+    pub fn bubble(thickness: M, index: Factor) -> Self {
+        Self::new(thickness, index, 1.0)
+    }
+
+    /// refraction coefficient of the ray passing through the film into the substrate,
+    /// or out of it when the ray is `inside`
+    pub fn factor(&self, inside: bool) -> Factor {
+        if inside {
+            self.substrate
+        } else {
+            1.0 / self.substrate
+        }
+    }
+
+    /// Airy formula for unpolarized light coming from the air,
+    /// or from the substrate when the ray is `inside`
+    pub fn reflectance(&self, wavelength: M, cosine: M, inside: bool) -> Density {
+        if inside {
+            // the film does not absorb, so it reflects the same part of the light
+            // coming from either side along the same path
+            let sin2 = self.substrate * self.substrate * (1.0 - cosine * cosine);
+            if sin2 >= 1.0 {
+                return 1.0;
+            }
+            return self.reflectance(wavelength, (1.0 - sin2).sqrt(), false);
+        }
+
+        let sin2 = 1.0 - cosine * cosine;
+
+        // cosines of the angles inside the film and the substrate multiplied by the indices
+        let n1c1 = self.index * self.index - sin2;
+        let n2c2 = self.substrate * self.substrate - sin2;
+        if n1c1 <= 0.0 || n2c2 <= 0.0 {
+            // total internal reflection, nothing is absorbed so everything goes back
+            return 1.0;
+        }
+        let (n1c1, n2c2) = (n1c1.sqrt(), n2c2.sqrt());
+        let (n0, n1, n2) = (1.0, self.index, self.substrate);
+        let (c0, c1, c2) = (cosine, n1c1 / n1, n2c2 / n2);
+
+        let phase = 4.0 * M_PI * self.thickness * n1c1 / wavelength;
+        let airy = |r01: M, r12: M| {
+            let interference = 2.0 * r01 * r12 * phase.cos();
+            (r01 * r01 + r12 * r12 + interference) / (1.0 + r01 * r01 * r12 * r12 + interference)
+        };
+
+        let s = airy(
+            (n0 * c0 - n1 * c1) / (n0 * c0 + n1 * c1),
+            (n1 * c1 - n2 * c2) / (n1 * c1 + n2 * c2),
+        );
+        let p = airy(
+            (n1 * c0 - n0 * c1) / (n1 * c0 + n0 * c1),
+            (n2 * c1 - n1 * c2) / (n2 * c1 + n1 * c2),
+        );
+
+        (s + p) / 2.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::beam::Material;
+    use super::super::fixture;
+    use super::super::ray::GeometricalRay;
+    use super::super::scene::Scene;
+
+    use rand;
+
+    #[test]
+    fn anti_reflection() {
+        let wavelength = 550.0;
+        let substrate: Factor = 1.52;
+        let index = substrate.sqrt();
+        let coating = ThinFilm::new(wavelength / (4.0 * index), index, substrate);
+        let bare = ThinFilm::new(0.0, index, substrate);
+
+        let fresnel = ((substrate - 1.0) / (substrate + 1.0)).powi(2);
+        assert!((bare.reflectance(wavelength, 1.0, false) - fresnel).abs() < 1.0e-9);
+        assert!(coating.reflectance(wavelength, 1.0, false) < 1.0e-9);
+        assert!(coating.reflectance(wavelength * 0.7, 1.0, false) > 1.0e-3);
+
+        // the ray leaving the substrate at the angle of the refracted one
+        let cosine: M = 0.8;
+        let inside = (1.0 - (1.0 - cosine * cosine) / (substrate * substrate)).sqrt();
+        let reflectance = coating.reflectance(wavelength * 0.7, cosine, false);
+        assert!((coating.reflectance(wavelength * 0.7, inside, true) - reflectance).abs() < 1.0e-9);
+        assert_eq!(coating.reflectance(wavelength, 0.5, true), 1.0);
+    }
+
+    #[test]
+    fn triangle() {
+        // the scaled normal of the triangle doesn't stretch the scattered ray
+        let mut rng = rand::thread_rng();
+        let coating = ThinFilm::new(100.0, 1.23, 1.52);
+        let scene = Scene::new(vec![], vec![fixture::floor(Material::film(coating))]);
+        let ray = fixture::ray();
+        let result = scene.intersect(&ray).unwrap();
+        for _ in 0..100 {
+            let bsdf = result.surface.bsdf();
+            let scattering = bsdf.sample(&ray, result.position, result.normal, &mut rng).unwrap();
+            assert!((scattering.ray.direction().length() - 1.0).abs() < 1.0e-9);
+        }
+    }
+}
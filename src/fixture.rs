@@ -0,0 +1,54 @@
+use super::algebra::V3;
+
+use super::beam::Beam;
+use super::beam::Density;
+use super::beam::Frequency;
+use super::beam::Material;
+
+use super::primitive::Sphere;
+use super::primitive::Triangle;
+
+use super::ray::Ray;
+
+use super::scene::Method;
+use super::scene::Scene;
+
+/// the floor at the height zero large enough to look infinite
+pub fn floor(material: Material) -> Triangle {
+    Triangle::new(
+        V3::new(-100.0, -100.0, 0.0),
+        V3::new(100.0, -100.0, 0.0),
+        V3::new(0.0, 100.0, 0.0),
+        material,
+    )
+}
+
+/// white lambertian floor under the spherical lamp
+pub fn lamp(method: Method) -> Scene {
+    let lamp = Sphere::new(V3::new(0.0, 0.0, 2.0), 1.0, Material::emission(Beam::flat(1.0)));
+    let floor = floor(Material::diffuse(Beam::flat(1.0)));
+    Scene::new(vec![lamp], vec![floor]).with_method(method)
+}
+
+/// the ray hitting the floor of the `lamp` scene where the light is seen in the cone
+pub fn ray() -> Ray {
+    let direction = V3::new(-1.0, 0.0, -1.0).normalize();
+    Ray::new(V3::new(3.0, 0.0, 3.0), direction, Frequency::new(0))
+}
+
+/// radiance the `ray` brings back in the `lamp` scene
+pub fn expected() -> Density {
+    1.0 - (3.0 as Density).sqrt() / 2.0
+}
+
+/// white lambertian floor under the triangular lamp facing it closely
+pub fn panel(method: Method) -> Scene {
+    let lamp = Triangle::new(
+        V3::new(-1.0, -1.0, 0.5),
+        V3::new(1.0, -1.0, 0.5),
+        V3::new(0.0, 1.5, 0.5),
+        Material::emission(Beam::flat(1.0)),
+    );
+    let floor = floor(Material::diffuse(Beam::flat(1.0)));
+    Scene::new(vec![], vec![lamp, floor]).with_method(method)
+}
@@ -0,0 +1,147 @@
+use super::beam::Beam;
+use super::beam::Frequency;
+use super::beam::Density;
+
+use super::spectrum;
+use super::spectrum::Spectrum;
+
+use std::error::Error;
+use std::fmt;
+
+/// Fluorescence struct is a re-radiation matrix, the row `i` is the spectrum
+/// re-emitted by the photon absorbed at the band `i`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Fluorescence {
+    #[serde(deserialize_with = "spectrum::bands")]
+    rows: Vec<Beam>,
+}
+
+/// FluorescenceError enum is why the matrix is not valid
+#[derive(Debug)]
+pub enum FluorescenceError {
+    /// the matrix should have one row for every band of the spectrum
+    Rows { rows: usize, bands: usize },
+}
+
+impl fmt::Display for FluorescenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FluorescenceError::Rows { rows: rows, bands: bands } => {
+                write!(f, "matrix has {} rows for {} bands", rows, bands)
+            }
+        }
+    }
+}
+
+impl Error for FluorescenceError {}
+
+impl Fluorescence {
+    pub fn new(rows: Vec<Beam>) -> Result<Self, FluorescenceError> {
+        let bands = Spectrum::current().size();
+        if rows.len() == bands {
+            Ok(Fluorescence { rows: rows })
+        } else {
+            Err(FluorescenceError::Rows {
+                rows: rows.len(),
+                bands: bands,
+            })
+        }
+    }
+
+    /// absorbs the photon with probability given by `absorption`
+    /// and re-emits it with spectrum shaped as `emission`
+    pub fn separable(absorption: Beam, emission: Beam) -> Self {
+        let total = Frequency::all()
+            .fold(0.0, |total, frequency| total + emission.density(&frequency));
+        let emission = emission * (1.0 / total);
+
+        let rows = Frequency::all()
+            .map(|frequency| emission.clone() * absorption.density(&frequency))
+            .collect();
+
+        Fluorescence { rows: rows }
+    }
+
+    /// probability that the ray observed at `emission` is continued at some excitation band
+    pub fn efficiency(&self, emission: &Frequency) -> Density {
+        self.rows.iter().fold(0.0, |sum, row| sum + row.density(emission))
+    }
+
+    /// rays are traced backward, so the ray observed at `emission` is continued
+    /// at the excitation band chosen with the probability of the matrix element,
+    /// the `fate` is a uniform random number consumed by the choice
+    pub fn excitation(&self, emission: &Frequency, fate: Density) -> Option<Frequency> {
+        let mut fate = fate;
+        if fate < 0.0 {
+            return None;
+        }
+
+        for (i, row) in self.rows.iter().enumerate() {
+            fate -= row.density(emission);
+            if fate < 0.0 {
+                return Some(Frequency::new(i));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use super::super::beam::Material;
+    use super::super::beam::SingleFate;
+
+    use rand;
+
+    #[test]
+    fn excitation() {
+        // the light absorbed in the short half is re-emitted evenly at every band
+        let size = Spectrum::current().size();
+        let absorption = Beam::from_bands(|f| if f.index() < size / 2 { 0.5 } else { 0.0 });
+        let fluorescence = Fluorescence::separable(absorption, Beam::flat(1.0));
+        let observed = Frequency::new(size - 1);
+        assert!((fluorescence.efficiency(&observed) - 0.25).abs() < 1.0e-9);
+
+        let samples = 1200;
+        let mut counts = vec![0; size];
+        for k in 0..samples {
+            let fate = ((k as Density) + 0.5) / (samples as Density) * 0.25;
+            counts[fluorescence.excitation(&observed, fate).unwrap().index()] += 1;
+        }
+        assert_eq!(fluorescence.excitation(&observed, 0.25 + 1.0e-9), None);
+        for (i, count) in counts.into_iter().enumerate() {
+            let expected = if i < size / 2 { samples / (size / 2) } else { 0 };
+            assert!((count as isize - expected as isize).abs() <= 1);
+        }
+
+        // the reflection of the same material keeps its probability
+        let mut rng = rand::thread_rng();
+        let reflection = Material::reflection(Beam::flat(0.25));
+        let material = Material::fluorescence(fluorescence) + reflection;
+        let (samples, mut reflected, mut excited) = (10000, 0, 0);
+        for _ in 0..samples {
+            let fate = material.fate(&observed, &mut rng);
+            assert!((fate.weight - 0.5).abs() < 1.0e-9);
+            match fate.single {
+                SingleFate::Reflect => reflected += 1,
+                SingleFate::Fluoresce(_) => excited += 1,
+                _ => panic!("the material only reflects and fluoresces"),
+            }
+        }
+        let half = (samples / 2) as Density;
+        assert!(((reflected as Density) - half).abs() < 0.05 * half);
+        assert!(((excited as Density) - half).abs() < 0.05 * half);
+    }
+
+    #[test]
+    fn rows() {
+        // the matrix needs one row for every band
+        let size = Spectrum::current().size();
+        assert!(Fluorescence::new(vec![Beam::flat(0.1); size]).is_ok());
+        assert!(Fluorescence::new(vec![Beam::flat(0.1); size - 1]).is_err());
+        assert!(Fluorescence::new(vec![Beam::flat(0.1); size + 1]).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use super::algebra::V3;
+use super::algebra::M;
+use super::algebra::M_PI;
+
+use super::beam::Density;
+
+use rand::Rng;
+use rand::distributions::Sample;
+use rand::distributions::Range;
+
+/// Grating struct is a reflective surface with grooves along the `axis`,
+/// the `spacing` between grooves is in nanometres
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Grating {
+    spacing: M,
+    axis: V3,
+    orders: usize,
+    #[serde(default)]
+    blaze: Option<M>,
+}
+
+impl Grating {
+    /// the ray is distributed evenly among orders from `-orders` to `orders`
+    pub fn new(spacing: M, axis: V3, orders: usize) -> Self {
+        Grating {
+            spacing: spacing,
+            axis: axis,
+            orders: orders,
+            blaze: None,
+        }
+    }
+
+    /// grooves blazed for the first order at the `wavelength` in nanometres,
+    /// the orders get the light by the scalar theory instead of evenly
+    pub fn with_blaze(self, wavelength: M) -> Self {
+        Grating {
+            blaze: Some(wavelength),
+            ..self
+        }
+    }
+
+    /// part of the light diffracted into the `order`, the sum over all orders is one
+    pub fn efficiency(&self, wavelength: M, order: isize) -> Density {
+        match self.blaze {
+            Some(blaze) => {
+                let x = M_PI * ((order as M) - blaze / wavelength);
+                if x.abs() < 1.0e-9 {
+                    1.0
+                } else {
+                    (x.sin() / x).powi(2)
+                }
+            }
+            None => 1.0 / ((2 * self.orders + 1) as Density),
+        }
+    }
+
+    /// compact disc has 1.6 micrometres track pitch
+    pub fn compact_disc(axis: V3) -> Self {
+        Self::new(1600.0, axis, 3)
+    }
+
+    /// direction of the ray diffracted into the order `order`, none if the order is evanescent
+    pub fn direction(&self, incident: V3, normal: V3, wavelength: M, order: isize) -> Option<V3> {
+        let normal = normal.normalize();
+        let across = normal.cross(self.axis);
+        if across.length() < 1.0e-9 {
+            return None;
+        }
+        let across = across.normalize();
+        let along = across.cross(normal);
+
+        // grating equation acts on the tangential component across the grooves
+        let incident = incident.normalize();
+        let x = incident * across + (order as M) * wavelength / self.spacing;
+        let y = incident * along;
+        let z = 1.0 - x * x - y * y;
+        if z < 0.0 {
+            None
+        } else {
+            let z = if incident * normal < 0.0 { z.sqrt() } else { -z.sqrt() };
+            Some(across * x + along * y + normal * z)
+        }
+    }
+
+    /// direction of the random order along with the part of the light carried by it,
+    /// the evanescent orders of the blazed grating take their light away
+    pub fn diffract(
+        &self,
+        incident: V3,
+        normal: V3,
+        wavelength: M,
+        mut rng: &mut dyn Rng,
+    ) -> Option<(V3, Density)> {
+        let orders = self.orders as isize;
+        let directions = (-orders..(orders + 1))
+            .filter_map(|order| {
+                let efficiency = self.efficiency(wavelength, order);
+                self.direction(incident, normal, wavelength, order)
+                    .map(|direction| (direction, efficiency))
+            })
+            .filter(|&(_, efficiency)| efficiency > 0.0)
+            .collect::<Vec<_>>();
+
+        let total = directions.iter().fold(0.0, |total, &(_, efficiency)| total + efficiency);
+        if total <= 0.0 {
+            return None;
+        }
+
+        // the order is chosen proportionally to its efficiency
+        let mut choice = Range::new(0.0, total).sample(&mut rng);
+        let mut chosen = directions[directions.len() - 1].0;
+        for &(direction, efficiency) in directions.iter() {
+            if choice < efficiency {
+                chosen = direction;
+                break;
+            }
+            choice -= efficiency;
+        }
+
+        match self.blaze {
+            Some(_) => Some((chosen, total)),
+            None => Some((chosen, 1.0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rand;
+
+    #[test]
+    fn orders() {
+        let grating = Grating::new(1000.0, V3::new(0.0, 1.0, 0.0), 2);
+        let normal = V3::new(0.0, 0.0, -1.0);
+        let incident = V3::new(0.0, 0.0, 1.0);
+
+        // zero order is a mirror reflection
+        let mirror = grating.direction(incident, normal, 500.0, 0).unwrap();
+        assert!((mirror * normal - 1.0).abs() < 1.0e-9);
+
+        // the first order obeys the grating equation and the second is evanescent
+        let first = grating.direction(incident, normal, 500.0, 1).unwrap();
+        assert!((first.length() - 1.0).abs() < 1.0e-9);
+        assert!(((first * V3::new(1.0, 0.0, 0.0)).abs() - 0.5).abs() < 1.0e-9);
+        assert!(grating.direction(incident, normal, 600.0, 2).is_none());
+
+        // the blazed grating sends everything into the first order at its wavelength
+        let mut rng = rand::thread_rng();
+        let blazed = grating.with_blaze(500.0);
+        assert!((blazed.efficiency(500.0, 1) - 1.0).abs() < 1.0e-9);
+        assert!(blazed.efficiency(500.0, 0).abs() < 1.0e-9);
+        for _ in 0..20 {
+            let (direction, weight) = blazed.diffract(incident, normal, 500.0, &mut rng).unwrap();
+            assert!((direction - first).length() < 1.0e-9);
+            assert!((weight - 1.0).abs() < 1.0e-9);
+        }
+        let (_, weight) = blazed.diffract(incident, normal, 700.0, &mut rng).unwrap();
+        assert!(weight > 0.5 && weight < 1.0);
+    }
+}
@@ -0,0 +1,210 @@
+use super::algebra::M;
+
+use super::beam::Beam;
+use super::beam::Density;
+use super::beam::Frequency;
+
+use super::color;
+
+/// Emission spectra of the real light sources, tables are from 380 to 780 nanometres
+impl Beam {
+    /// Planck's law for the black body at the `temperature` in kelvins
+    pub fn blackbody(temperature: M) -> Self {
+        // second radiation constant in nanometre kelvins
+        let c2 = 1.438_776_877e7;
+        let peak = 2.897_771_955e6 / temperature;
+        let planck = |wavelength: M| {
+            (peak / wavelength).powi(5) * ((c2 / (peak * temperature)).exp() - 1.0)
+                / ((c2 / (wavelength * temperature)).exp() - 1.0)
+        };
+        Self::from_function(planck)
+    }
+
+    /// CIE daylight at the correlated color `temperature` from 4000 to 25000 kelvins
+    pub fn daylight(temperature: M) -> Self {
+        Self::from_function(|wavelength| daylight(temperature, wavelength))
+    }
+
+    /// noon daylight
+    pub fn d65() -> Self {
+        Self::daylight(6504.0)
+    }
+
+    /// horizon light
+    pub fn d50() -> Self {
+        Self::daylight(5003.0)
+    }
+
+    /// incandescent tungsten lamp
+    pub fn a() -> Self {
+        Self::blackbody(2856.0)
+    }
+
+    /// cool white fluorescent lamp
+    pub fn f2() -> Self {
+        let table = f2_table();
+        Self::from_function(|wavelength| tabulated(&table, 5.0, wavelength, |&value| value))
+    }
+
+    /// narrow band white fluorescent lamp
+    pub fn f11() -> Self {
+        let table = f11_table();
+        Self::from_function(|wavelength| tabulated(&table, 5.0, wavelength, |&value| value))
+    }
+
+    /// luminance weighted by the photopic luminous efficiency,
+    /// it is one for the flat spectrum of density one
+    pub fn luminance(&self) -> Density {
+        let (weighted, total) = Frequency::all().fold((0.0, 0.0), |(weighted, total), frequency| {
+            let (shortest, longest) = frequency.bounds();
+            let weight = color::luminosity(frequency.wavelength()) * (longest - shortest);
+            (weighted + self.density(&frequency) * weight, total + weight)
+        });
+
+        weighted / total
+    }
+
+    /// the same spectrum scaled to have the given luminance
+    pub fn with_luminance(self, luminance: Density) -> Self {
+        let current = self.luminance();
+        self * (luminance / current)
+    }
+}
+
+/// relative spectral power of the CIE daylight, the wavelength is in nanometres
+pub fn daylight(temperature: M, wavelength: M) -> Density {
+    let t = temperature;
+    let x = if t <= 7000.0 {
+        -4.6070e9 / (t * t * t) + 2.9678e6 / (t * t) + 0.09911e3 / t + 0.244063
+    } else {
+        -2.0064e9 / (t * t * t) + 1.9018e6 / (t * t) + 0.24748e3 / t + 0.237040
+    };
+    let y = -3.0 * x * x + 2.870 * x - 0.275;
+    let m = 0.0241 + 0.2562 * x - 0.7341 * y;
+    let m1 = (-1.3515 - 1.7703 * x + 5.9114 * y) / m;
+    let m2 = (0.0300 - 31.4424 * x + 30.0717 * y) / m;
+
+    let table = daylight_table();
+    tabulated(&table, 10.0, wavelength, |&(s0, s1, s2)| s0 + m1 * s1 + m2 * s2)
+}
+
+// linear interpolation in the table starting from 380 nanometres, zero outside
+fn tabulated<T, F>(table: &[T], step: M, wavelength: M, value: F) -> Density
+where
+    F: Fn(&T) -> Density,
+{
+    let position = (wavelength - 380.0) / step;
+    if position < 0.0 || position > (table.len() - 1) as M {
+        0.0
+    } else {
+        let i = (position as usize).min(table.len() - 2);
+        let t = position - i as M;
+        value(&table[i]) * (1.0 - t) + value(&table[i + 1]) * t
+    }
+}
+
+#[inline(always)]
+fn daylight_table() -> [(M, M, M); 41] {
+    [
+        (63.4, 38.5, 3.0),
+        (65.8, 35.0, 1.2),
+        (94.8, 43.4, -1.1),
+        (104.8, 46.3, -0.5),
+        (105.9, 43.9, -0.7),
+        (96.8, 37.1, -1.2),
+        (113.9, 36.7, -2.6),
+        (125.6, 35.9, -2.9),
+        (125.5, 32.6, -2.8),
+        (121.3, 27.9, -2.6),
+        (121.3, 24.3, -2.6),
+        (113.5, 20.1, -1.8),
+        (113.1, 16.2, -1.5),
+        (110.8, 13.2, -1.3),
+        (106.5, 8.6, -1.2),
+        (108.8, 6.1, -1.0),
+        (105.3, 4.2, -0.5),
+        (104.4, 1.9, -0.3),
+        (100.0, 0.0, 0.0),
+        (96.0, -1.6, 0.2),
+        (95.1, -3.5, 0.5),
+        (89.1, -3.5, 2.1),
+        (90.5, -5.8, 3.2),
+        (90.3, -7.2, 4.1),
+        (88.4, -8.6, 4.7),
+        (84.0, -9.5, 5.1),
+        (85.1, -10.9, 6.7),
+        (81.9, -10.7, 7.3),
+        (82.6, -12.0, 8.6),
+        (84.9, -14.0, 9.8),
+        (81.3, -13.6, 10.2),
+        (71.9, -12.0, 8.3),
+        (74.3, -13.3, 9.6),
+        (76.4, -12.9, 8.5),
+        (63.3, -10.6, 7.0),
+        (71.7, -11.6, 7.6),
+        (77.0, -12.2, 8.0),
+        (65.2, -10.2, 6.7),
+        (47.7, -7.8, 5.2),
+        (68.6, -11.2, 7.4),
+        (65.0, -10.4, 6.8),
+    ]
+}
+
+#[inline(always)]
+fn f2_table() -> [M; 81] {
+    [
+        1.18, 1.48, 1.84, 2.15, 3.44, 15.69, 3.85, 3.74, 4.19, 4.62,
+        5.06, 34.98, 11.81, 6.27, 6.63, 6.93, 7.19, 7.40, 7.54, 7.62,
+        7.65, 7.62, 7.62, 7.45, 7.28, 7.15, 7.05, 7.04, 7.16, 7.47,
+        8.04, 8.88, 10.01, 24.88, 16.64, 14.59, 16.16, 17.56, 18.62, 21.47,
+        22.79, 19.29, 18.66, 17.73, 16.54, 15.21, 13.80, 12.36, 10.95, 9.65,
+        8.40, 7.32, 6.31, 5.43, 4.68, 4.02, 3.45, 2.96, 2.55, 2.19,
+        1.89, 1.64, 1.53, 1.27, 1.10, 0.99, 0.88, 0.76, 0.68, 0.61,
+        0.56, 0.54, 0.51, 0.47, 0.47, 0.43, 0.46, 0.47, 0.40, 0.33,
+        0.27,
+    ]
+}
+
+#[inline(always)]
+fn f11_table() -> [M; 81] {
+    [
+        0.91, 0.63, 0.46, 0.37, 1.29, 12.68, 1.59, 1.79, 2.46, 3.33,
+        4.49, 33.94, 12.13, 6.95, 7.19, 7.12, 6.72, 6.13, 5.46, 4.79,
+        5.66, 14.29, 14.96, 8.97, 4.72, 2.33, 1.47, 1.10, 0.89, 0.83,
+        1.18, 4.90, 39.59, 72.84, 32.61, 7.52, 2.83, 1.96, 1.67, 4.43,
+        11.28, 14.76, 12.73, 9.74, 7.33, 9.72, 55.27, 42.58, 13.18, 13.16,
+        12.26, 5.11, 2.07, 2.34, 3.58, 3.01, 2.48, 2.14, 1.54, 1.33,
+        1.46, 1.94, 2.00, 1.20, 1.35, 4.10, 5.58, 2.51, 0.57, 0.27,
+        0.23, 0.21, 0.24, 0.24, 0.20, 0.24, 0.32, 0.26, 0.16, 0.12,
+        0.09,
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn luminance() {
+        assert!((Beam::flat(1.0).luminance() - 1.0).abs() < 1.0e-9);
+
+        let sources = [Beam::d65(), Beam::d50(), Beam::a(), Beam::f2(), Beam::f11()];
+        for source in sources.iter() {
+            assert!((source.clone().with_luminance(2.0).luminance() - 2.0).abs() < 1.0e-9);
+        }
+    }
+
+    #[test]
+    fn daylight() {
+        // relative to 560 nanometres, the values are from the CIE D65 table, bands smooth them
+        let d65 = Beam::d65();
+        let at = |wavelength: M| d65.density(&Frequency::from_wavelength(wavelength).unwrap());
+        let relative = at(460.0) / at(560.0);
+        assert!((relative - 1.178).abs() < 0.05);
+
+        // black body is warmer, so it is weaker at short wavelengths
+        let a = Beam::a();
+        let at = |wavelength: M| a.density(&Frequency::from_wavelength(wavelength).unwrap());
+        assert!(at(460.0) / at(560.0) < 0.5);
+    }
+}
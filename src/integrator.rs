@@ -0,0 +1,419 @@
+use super::algebra::V3;
+use super::algebra::M;
+
+use super::beam::Density;
+use super::beam::Frequency;
+
+use super::primitive::IntersectResult;
+
+use super::ray;
+use super::ray::Ray;
+use super::ray::PhotonicRay;
+use super::ray::GeometricalRay;
+
+use super::scene::Scene;
+use super::screen::Screen;
+
+use super::bidirectional;
+
+use std::error::Error;
+use std::fmt;
+use std::ptr;
+
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::de::Error as DeError;
+
+use rand::Rng;
+use rand::distributions::Sample;
+use rand::distributions::Range;
+
+/// Integrator trait is the algorithm finding the light the eye gets along the ray,
+/// `Screen::sample` uses the method of the scene, see `Method::custom`
+pub trait Integrator: Send + Sync {
+    /// integrator tracing the rays of the pass numbered from zero, the progressive algorithms
+    /// keep the state of the pass in it, the caller owns it, so the passes of different images
+    /// do not interfere; none if every pass is traced by this integrator,
+    /// the `screen` is none when the rays do not come from its eye, like in `Patch::measure`
+    fn pass(
+        &self,
+        _: &Scene,
+        _: Option<&Screen>,
+        _: usize,
+        _: &mut dyn Rng,
+    ) -> Option<Box<dyn Integrator>> {
+        None
+    }
+
+    /// radiance coming back along the `ray` at its single band, the emission found
+    /// by the path is weighted by the throughput of the scatterings before it, the throughput
+    /// is a scalar of that band, the other bands are traced by the other rays
+    fn trace(&self, scene: &Scene, ray: &Ray, rng: &mut dyn Rng) -> Density;
+
+    /// light the pass finds without tracing the rays from the eye, the `splat` gets the pixel,
+    /// the band and the radiance added to the mean of the pixel at that band
+    fn splat(&self, _: &Scene, _: &mut dyn Rng, _: &mut dyn FnMut(usize, &Frequency, Density)) {}
+}
+
+/// Depth struct limits the number of the vertices of the path traced from the eye or the light,
+/// after the `minimal` one the path is terminated randomly as its throughput is low
+/// and the survived path is brighter, so the result is not changed
+#[derive(Clone, Serialize)]
+pub struct Depth {
+    minimal: usize,
+    maximal: usize,
+}
+
+/// DepthError enum is why the depth is not valid
+#[derive(Debug)]
+pub enum DepthError {
+    /// the path has no vertex
+    Empty,
+    /// the roulette would start after the path is cut off
+    Inverted { minimal: usize, maximal: usize },
+}
+
+impl fmt::Display for DepthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DepthError::Empty => write!(f, "maximal depth should be at least one"),
+            DepthError::Inverted { minimal: minimal, maximal: maximal } => write!(
+                f,
+                "minimal depth {} is greater than the maximal one {}",
+                minimal, maximal
+            ),
+        }
+    }
+}
+
+impl Error for DepthError {}
+
+impl Default for Depth {
+    fn default() -> Self {
+        Depth {
+            minimal: 0,
+            maximal: 7,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Depth {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Raw {
+            minimal: usize,
+            maximal: usize,
+        }
+
+        let raw = Raw::deserialize(deserializer)?;
+        Depth::new(raw.minimal, raw.maximal).map_err(D::Error::custom)
+    }
+}
+
+impl Depth {
+    /// the `maximal` depth cuts the light of the longer paths off,
+    /// it should be large for many refractions
+    pub fn new(minimal: usize, maximal: usize) -> Result<Self, DepthError> {
+        if maximal < 1 {
+            Err(DepthError::Empty)
+        } else if minimal > maximal {
+            Err(DepthError::Inverted {
+                minimal: minimal,
+                maximal: maximal,
+            })
+        } else {
+            Ok(Depth {
+                minimal: minimal,
+                maximal: maximal,
+            })
+        }
+    }
+
+    pub fn minimal(&self) -> usize {
+        self.minimal
+    }
+
+    pub fn maximal(&self) -> usize {
+        self.maximal
+    }
+
+    /// russian roulette after the vertex at the `level` numbered from zero, returns
+    /// the throughput of the continued path, none if it is terminated
+    pub fn survive(
+        &self,
+        level: usize,
+        throughput: Density,
+        mut rng: &mut dyn Rng,
+    ) -> Option<Density> {
+        if level + 1 >= self.maximal || throughput <= 0.0 {
+            None
+        } else if level + 1 < self.minimal {
+            Some(throughput)
+        } else {
+            let probability = throughput.min(1.0);
+            if Range::new(0.0, 1.0).sample(&mut rng) < probability {
+                Some(throughput / probability)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+/// PathTracing struct is the default algorithm, the ray is continued by the bsdf
+/// and the light is sampled at every vertex
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct PathTracing {
+    #[serde(default)]
+    depth: Depth,
+}
+
+// previous scattering chosen from the component included in `Bsdf::evaluate`
+struct Previous {
+    position: V3,
+    pdf: Density,
+}
+
+impl Integrator for PathTracing {
+    fn trace(&self, scene: &Scene, ray: &Ray, rng: &mut dyn Rng) -> Density {
+        self.trace_internal(scene, ray, rng, 0, 1.0, None)
+    }
+}
+
+impl PathTracing {
+    pub fn with_depth(self, depth: Depth) -> Self {
+        PathTracing { depth: depth }
+    }
+
+    // the emission found by the non-singular scattering is weighted against the light sampling,
+    // the `throughput` is the product of the weights of the scatterings and the roulette
+    fn trace_internal(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        mut rng: &mut dyn Rng,
+        level: usize,
+        throughput: Density,
+        previous: Option<Previous>,
+    ) -> Density {
+        let depth = &self.depth;
+        let mut radiance = 0.0;
+
+        if level < depth.maximal() {
+            if let Some(result) = scene.intersect(ray) {
+                let bsdf = result.surface.bsdf();
+                let emission = bsdf.emission(&ray.frequency());
+                let weight = match previous {
+                    Some(ref previous) if emission > 0.0 => {
+                        let (surface, direction) = (result.surface, ray.direction());
+                        let light = scene.light_pdf(surface, previous.position, direction);
+                        scene.heuristic().weight(previous.pdf, light)
+                    }
+                    _ => 1.0,
+                };
+                // the bsdf sampling cannot find the light past the last vertex
+                let last = level + 1 >= depth.maximal();
+                radiance += emission * weight * throughput
+                    + self.sample_light(scene, ray, &result, throughput, last, &mut rng);
+
+                let scattering = bsdf.sample(ray, result.position, result.normal, &mut rng);
+                if let Some(scattering) = scattering {
+                    let throughput = throughput * scattering.weight;
+                    if let Some(throughput) = depth.survive(level, throughput, &mut rng) {
+                        let previous = if scattering.singular {
+                            None
+                        } else {
+                            let outgoing = scattering.ray.direction();
+                            let frequency = ray.frequency();
+                            Some(Previous {
+                                position: result.position,
+                                pdf: bsdf.pdf(ray.direction(), outgoing, result.normal, &frequency),
+                            })
+                        };
+                        let ray = scattering.ray;
+                        let level = level + 1;
+                        radiance +=
+                            self.trace_internal(scene, &ray, rng, level, throughput, previous);
+                    }
+                }
+            }
+        }
+
+        radiance
+    }
+
+    // next event estimation, the shadow ray goes toward the random emitter,
+    // the contribution is weighted against the bsdf sampling unless the vertex is the `last`
+    fn sample_light(
+        &self,
+        scene: &Scene,
+        ray: &Ray,
+        result: &IntersectResult,
+        throughput: Density,
+        last: bool,
+        mut rng: &mut dyn Rng,
+    ) -> Density {
+        let count = scene.emitter_count();
+        if count == 0 {
+            return 0.0;
+        }
+
+        let primitive = scene.emitter(Range::new(0, count).sample(&mut rng));
+        let (direction, pdf) = match primitive.sample_direction(result.position, &mut rng) {
+            Some(sample) => sample,
+            None => return 0.0,
+        };
+
+        let bsdf = result.surface.bsdf();
+        let frequency = ray.frequency();
+        let value = bsdf.evaluate(ray.direction(), direction, result.normal, &frequency);
+        if value <= 0.0 || pdf <= 0.0 {
+            return 0.0;
+        }
+
+        let shadow = Ray::new(result.position + direction * Ray::EPS, direction, frequency.clone());
+        match scene.intersect(&shadow) {
+            Some(ref light) if ptr::eq(light.surface, primitive.surface()) => {
+                let emission = light.surface.bsdf().emission(&frequency);
+                let pdf = pdf / (count as Density);
+                let weight = if last {
+                    1.0
+                } else {
+                    let other = bsdf.pdf(ray.direction(), direction, result.normal, &frequency);
+                    scene.heuristic().weight(pdf, other)
+                };
+                emission * value / pdf * weight * throughput
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// AmbientOcclusion struct is the debug algorithm, the surface is as bright
+/// as the part of the hemisphere above it not occluded closer than the `distance`
+#[derive(Clone)]
+pub struct AmbientOcclusion {
+    distance: M,
+}
+
+impl AmbientOcclusion {
+    pub fn new(distance: M) -> Self {
+        AmbientOcclusion { distance: distance }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn trace(&self, scene: &Scene, ray: &Ray, mut rng: &mut dyn Rng) -> Density {
+        let result = match scene.intersect(ray) {
+            Some(result) => result,
+            None => return 0.0,
+        };
+
+        let normal = bidirectional::facing(result.normal.normalize(), ray.direction());
+        let direction = ray::cosine_direction(normal, &mut rng);
+        let shadow = Ray::new(result.position + direction * Ray::EPS, direction, ray.frequency());
+        match scene.intersect(&shadow) {
+            Some(ref hit) if (hit.position - result.position).length() < self.distance => 0.0,
+            _ => 1.0,
+        }
+    }
+}
+
+/// Facing struct is the debug algorithm, the surface is as bright
+/// as it faces the ray, the light and the materials are ignored
+#[derive(Default, Clone)]
+pub struct Facing;
+
+impl Integrator for Facing {
+    fn trace(&self, scene: &Scene, ray: &Ray, _: &mut dyn Rng) -> Density {
+        match scene.intersect(ray) {
+            Some(result) => (result.normal.normalize() * ray.direction().normalize()).abs(),
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use super::super::fixture;
+    use super::super::beam::Material;
+    use super::super::beam::Frequency;
+    use super::super::primitive::Sphere;
+    use super::super::scene::Heuristic;
+    use super::super::scene::Method;
+
+    use serde::de::value;
+    use serde::de::value::MapDeserializer;
+
+    use rand;
+
+    #[test]
+    fn ambient_occlusion() {
+        // the floor under the large ball is darker than the open floor
+        let mut rng = rand::thread_rng();
+        let ball = Sphere::new(V3::new(0.0, 0.0, 1.2), 1.0, Material::default());
+        let scene = Scene::new(vec![ball], vec![fixture::floor(Material::default())]);
+        let integrator = AmbientOcclusion::new(10.0);
+
+        let samples = 2000;
+        let mean = |position: V3, rng: &mut dyn Rng| {
+            let ray = Ray::new(position, V3::new(0.0, 0.0, -1.0), Frequency::new(0));
+            let sum = (0..samples).fold(0.0, |sum, _| sum + integrator.trace(&scene, &ray, rng));
+            sum / (samples as Density)
+        };
+
+        assert_eq!(mean(V3::new(20.0, 0.0, 1.0), &mut rng), 1.0);
+        let covered = mean(V3::new(0.0, 0.0, 0.1), &mut rng);
+        assert!(covered > 0.0 && covered < 0.5);
+    }
+
+    #[test]
+    fn last_vertex() {
+        // the light sampled at the only vertex isn't shared with the bsdf sampling cut off there
+        let mut rng = rand::thread_rng();
+        let path = PathTracing::default().with_depth(Depth::new(1, 1).unwrap());
+        let scene = fixture::lamp(Method::PathTracing(path)).with_heuristic(Heuristic::Balance);
+
+        let samples = 1000;
+        let ray = fixture::ray();
+        let sum = (0..samples).fold(0.0, |sum, _| sum + scene.trace(&ray, &mut rng));
+        assert!((sum / (samples as Density) - fixture::expected()).abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn roulette() {
+        // the terminated paths are made up by the survived ones
+        let mut rng = rand::thread_rng();
+        let depth = Depth::new(2, 8).unwrap();
+        assert_eq!(depth.survive(0, 0.25, &mut rng), Some(0.25));
+        assert_eq!(depth.survive(7, 1.0, &mut rng), None);
+
+        let samples = 100000;
+        let sum = (0..samples).fold(0.0, |sum, _| {
+            sum + depth.survive(3, 0.25, &mut rng).unwrap_or(0.0)
+        });
+        assert!((sum / (samples as Density) - 0.25).abs() < 0.01);
+    }
+
+    #[test]
+    fn depth() {
+        // the invalid depth is rejected by the constructor and the deserializer
+        assert!(Depth::new(0, 0).is_err());
+        assert!(Depth::new(3, 2).is_err());
+        assert!(Depth::new(2, 2).is_ok());
+
+        let deserialize = |minimal: usize, maximal: usize| {
+            let fields = vec![("minimal", minimal), ("maximal", maximal)];
+            let deserializer = MapDeserializer::<_, value::Error>::new(fields.into_iter());
+            Depth::deserialize(deserializer).map(|depth| (depth.minimal(), depth.maximal()))
+        };
+        assert_eq!(deserialize(1, 4).ok(), Some((1, 4)));
+        assert!(deserialize(5, 4).is_err());
+        assert!(deserialize(0, 0).is_err());
+    }
+}
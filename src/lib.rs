@@ -1,27 +1,93 @@
 #![forbid(unsafe_code)]
 #![allow(non_shorthand_field_patterns)]
+#![allow(clippy::redundant_field_names)]
 
 #[macro_use]
 extern crate serde_derive;
 
+extern crate serde;
+
 extern crate rand;
 
 mod algebra;
 mod beam;
+mod bsdf;
 mod primitive;
 mod screen;
 mod scene;
 mod ray;
 mod color;
+mod colorspace;
+mod spectrum;
+mod dispersion;
+mod film;
+mod fluorescence;
+mod grating;
+mod illuminant;
+mod upsampling;
+mod polarization;
+mod sensor;
+mod measured;
+mod photometry;
+mod meter;
+mod bidirectional;
+mod photon;
+mod metropolis;
+mod integrator;
+
+#[cfg(test)]
+mod fixture;
 
 pub use self::algebra::V3;
 pub use self::beam::Beam;
 pub use self::beam::BeamRefract;
 pub use self::beam::Material;
+pub use self::beam::Frequency;
+pub use self::spectrum::Spectrum;
+pub use self::spectrum::SpectrumError;
+pub use self::colorspace::Observer;
+pub use self::colorspace::ColorSpace;
+pub use self::colorspace::XYZ;
+pub use self::colorspace::Adaptation;
+pub use self::colorspace::WhiteBalance;
+pub use self::sensor::Sensor;
+pub use self::sensor::SensorKind;
+pub use self::measured::MeasuredError;
+pub use self::photometry::Calibration;
+pub use self::photometry::LUMINOUS_EFFICACY;
+pub use self::meter::Patch;
+pub use self::meter::Measurement;
+pub use self::bsdf::Bsdf;
+pub use self::bsdf::Surface;
+pub use self::film::ThinFilm;
+pub use self::fluorescence::Fluorescence;
+pub use self::fluorescence::FluorescenceError;
+pub use self::grating::Grating;
+pub use self::polarization::Stokes;
+pub use self::polarization::Mueller;
+pub use self::polarization::PolarizingFilter;
 pub use self::scene::Scene;
+pub use self::scene::Heuristic;
+pub use self::scene::Method;
+pub use self::bidirectional::Bidirectional;
+pub use self::photon::PhotonMapping;
+pub use self::metropolis::Metropolis;
+pub use self::integrator::Integrator;
+pub use self::integrator::Depth;
+pub use self::integrator::DepthError;
+pub use self::integrator::PathTracing;
+pub use self::integrator::AmbientOcclusion;
+pub use self::integrator::Facing;
+pub use self::primitive::Primitive;
+pub use self::primitive::IntersectResult;
 pub use self::primitive::Sphere;
 pub use self::primitive::Triangle;
 pub use self::screen::Screen;
 pub use self::screen::Image;
+pub use self::screen::ImageError;
 pub use self::screen::Eye;
 pub use self::screen::Size;
+pub use self::ray::Ray;
+pub use self::ray::PhotonicRay;
+pub use self::ray::GeometricalRay;
+pub use self::ray::PolarizedRay;
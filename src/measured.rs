@@ -0,0 +1,218 @@
+use super::algebra::M;
+
+use super::beam::Beam;
+use super::beam::BeamRefract;
+use super::beam::Frequency;
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+/// MeasuredError enum is why the measured spectrum cannot be imported
+#[derive(Debug)]
+pub enum MeasuredError {
+    Io(io::Error),
+    /// the line (counting from one) is not a pair of numbers
+    Malformed { line: usize },
+    /// wavelengths should strictly increase, the line counts from one
+    Unsorted { line: usize },
+    /// less than two samples
+    Empty,
+    /// the measurement does not cover the band from `shortest` to `longest` nanometres
+    OutOfRange { shortest: M, longest: M },
+    /// the refraction index at the wavelength in nanometres is not positive
+    NonPositive { wavelength: M },
+}
+
+impl fmt::Display for MeasuredError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MeasuredError::Io(ref error) => write!(f, "cannot read the spectrum: {}", error),
+            MeasuredError::Malformed { line: line } => {
+                write!(f, "line {} is not a wavelength and a value", line)
+            }
+            MeasuredError::Unsorted { line: line } => {
+                write!(f, "wavelength at line {} does not increase", line)
+            }
+            MeasuredError::Empty => write!(f, "spectrum needs at least two samples"),
+            MeasuredError::OutOfRange { shortest: shortest, longest: longest } => write!(
+                f,
+                "band from {:.1} to {:.1} nm is not measured",
+                shortest, longest
+            ),
+            MeasuredError::NonPositive { wavelength: wavelength } => {
+                write!(f, "refraction index at {:.1} nm is not positive", wavelength)
+            }
+        }
+    }
+}
+
+impl Error for MeasuredError {}
+
+impl From<io::Error> for MeasuredError {
+    fn from(error: io::Error) -> Self {
+        MeasuredError::Io(error)
+    }
+}
+
+/// Measured spectra given as `wavelength,value` lines, wavelengths are in nanometres,
+/// the separator is comma, semicolon or whitespace, lines starting with `#` are comments
+/// and the first line might be a header
+impl Beam {
+    pub fn from_csv<R>(reader: R) -> Result<Self, MeasuredError>
+    where
+        R: Read,
+    {
+        let samples = parse(reader)?;
+        let averages = averages(&samples)?;
+        Ok(Self::from_bands(|frequency| averages[frequency.index()]))
+    }
+
+    pub fn load_csv<P>(path: P) -> Result<Self, MeasuredError>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_csv(File::open(path)?)
+    }
+}
+
+/// Measured refraction index given the same way as the spectrum
+impl BeamRefract {
+    pub fn from_csv<R>(reader: R) -> Result<Self, MeasuredError>
+    where
+        R: Read,
+    {
+        let samples = parse(reader)?;
+        if let Some(&(wavelength, _)) = samples.iter().find(|&&(_, index)| index <= 0.0) {
+            return Err(MeasuredError::NonPositive { wavelength: wavelength });
+        }
+        let averages = averages(&samples)?;
+        Ok(Self::from_band_index(|frequency| averages[frequency.index()]))
+    }
+
+    pub fn load_csv<P>(path: P) -> Result<Self, MeasuredError>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_csv(File::open(path)?)
+    }
+}
+
+fn parse<R>(mut reader: R) -> Result<Vec<(M, M)>, MeasuredError>
+where
+    R: Read,
+{
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+
+    let mut samples: Vec<(M, M)> = Vec::new();
+    let mut first = true;
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields = line
+            .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+            .filter(|field| !field.is_empty())
+            .map(|field| field.parse::<M>())
+            .collect::<Vec<_>>();
+        let sample = match fields.as_slice() {
+            [Ok(wavelength), Ok(value)] if wavelength.is_finite() && value.is_finite() => {
+                (*wavelength, *value)
+            }
+            _ if first && fields.iter().all(|field| field.is_err()) => {
+                first = false;
+                continue;
+            }
+            _ => return Err(MeasuredError::Malformed { line: number + 1 }),
+        };
+        first = false;
+
+        if let Some(&(last, _)) = samples.last() {
+            if sample.0 <= last {
+                return Err(MeasuredError::Unsorted { line: number + 1 });
+            }
+        }
+        samples.push(sample);
+    }
+
+    if samples.len() < 2 {
+        Err(MeasuredError::Empty)
+    } else {
+        Ok(samples)
+    }
+}
+
+// mean of the linearly interpolated samples over every band
+fn averages(samples: &[(M, M)]) -> Result<Vec<M>, MeasuredError> {
+    let (first, last) = (samples[0].0, samples[samples.len() - 1].0);
+    let value = |wavelength: M, a: (M, M), b: (M, M)| {
+        a.1 + (b.1 - a.1) * (wavelength - a.0) / (b.0 - a.0)
+    };
+
+    Frequency::all()
+        .map(|frequency| {
+            let (shortest, longest) = frequency.bounds();
+            if shortest < first || longest > last {
+                return Err(MeasuredError::OutOfRange {
+                    shortest: shortest,
+                    longest: longest,
+                });
+            }
+
+            // trapezoids of the segments clipped by the band
+            let integral = samples.windows(2).fold(0.0, |sum, segment| {
+                let (a, b) = (segment[0], segment[1]);
+                let (left, right) = (a.0.max(shortest), b.0.min(longest));
+                if left < right {
+                    sum + (value(left, a, b) + value(right, a, b)) * (right - left) / 2.0
+                } else {
+                    sum
+                }
+            });
+
+            Ok(integral / (longest - shortest))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn import() {
+        let csv = "wavelength,reflectance\n# measured\n300,0.0\n800,1.0\n";
+        let beam = Beam::from_csv(csv.as_bytes()).unwrap();
+        for frequency in Frequency::all() {
+            let (shortest, longest) = frequency.bounds();
+            let expected = ((shortest + longest) / 2.0 - 300.0) / 500.0;
+            assert!((beam.density(&frequency) - expected).abs() < 1.0e-9);
+        }
+
+        let index = BeamRefract::from_csv("300 1.5\n800 1.5".as_bytes()).unwrap();
+        assert!((index.factor(&Frequency::new(0)) - 1.0 / 1.5).abs() < 1.0e-9);
+
+        match Beam::from_csv("300,0.0\n800,x\n".as_bytes()) {
+            Err(MeasuredError::Malformed { line: 2 }) => (),
+            _ => panic!("malformed line is accepted"),
+        }
+        match Beam::from_csv("300,0.0\n200,1.0\n".as_bytes()) {
+            Err(MeasuredError::Unsorted { line: 2 }) => (),
+            _ => panic!("unsorted lines are accepted"),
+        }
+        match Beam::from_csv("500,0.0\n800,1.0\n".as_bytes()) {
+            Err(MeasuredError::OutOfRange { .. }) => (),
+            _ => panic!("short measurement is accepted"),
+        }
+        match BeamRefract::from_csv("300 1.5\n500 0.0\n800 1.5".as_bytes()) {
+            Err(MeasuredError::NonPositive { wavelength }) => assert_eq!(wavelength, 500.0),
+            _ => panic!("zero refraction index is accepted"),
+        }
+    }
+}
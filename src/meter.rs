@@ -0,0 +1,132 @@
+use super::algebra::V3;
+use super::algebra::M_PI;
+
+use super::beam::Beam;
+use super::beam::Density;
+
+use super::ray;
+use super::ray::Ray;
+use super::scene::Scene;
+use super::integrator::Integrator;
+
+use super::photometry::Calibration;
+
+use rand::Rng;
+
+/// Patch struct is a small oriented measurement area in the scene, like a lux meter
+/// lying on a desk, it does not block the light
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Patch {
+    position: V3,
+    normal: V3,
+}
+
+/// Measurement struct is the result of the patch, the intervals are 95% confidence
+pub struct Measurement {
+    /// spectral irradiance in W/(m² nm) of every band
+    pub irradiance: Beam,
+    /// standard error of the spectral irradiance
+    pub irradiance_error: Beam,
+    /// illuminance in lux
+    pub illuminance: Density,
+    pub illuminance_interval: (Density, Density),
+}
+
+impl Patch {
+    pub fn new(position: V3, normal: V3) -> Self {
+        Patch {
+            position: position,
+            normal: normal.normalize(),
+        }
+    }
+
+    /// trace `samples` cosine distributed rays from the patch at every band,
+    /// the irradiance is pi times the mean radiance
+    pub fn measure(
+        &self,
+        scene: &Scene,
+        samples: usize,
+        calibration: &Calibration,
+        mut rng: &mut dyn Rng,
+    ) -> Measurement {
+        let scale = M_PI * calibration.radiance();
+        let method = scene.method();
+        let pass = method.pass(scene, None, 0, &mut rng);
+        let integrator = pass.as_ref().map(|pass| pass.as_ref()).unwrap_or(method);
+
+        let (sum, square, illuminance, illuminance_square) = (0..samples).fold(
+            (Beam::default(), Beam::default(), 0.0, 0.0),
+            |(sum, square, illuminance, illuminance_square), _| {
+                let radiance = Beam::from_bands(|frequency| {
+                    let direction = ray::cosine_direction(self.normal, &mut rng);
+                    let position = self.position + direction * Ray::EPS;
+                    let ray = Ray::new(position, direction, frequency.clone());
+                    integrator.trace(scene, &ray, &mut rng)
+                });
+
+                let irradiance = radiance * scale;
+                let sample = calibration.luminance(&irradiance) / calibration.radiance();
+                let squared = Beam::from_bands(|f| irradiance.density(f).powi(2));
+                (
+                    sum + irradiance,
+                    square + squared,
+                    illuminance + sample,
+                    illuminance_square + sample * sample,
+                )
+            },
+        );
+
+        let n = samples.max(1) as Density;
+        let mean = sum * (1.0 / n);
+        let error = Beam::from_bands(|f| standard_error(mean.density(f), square.density(f), n));
+
+        let illuminance = illuminance / n;
+        let margin = 1.96 * standard_error(illuminance, illuminance_square, n);
+
+        Measurement {
+            irradiance: mean,
+            irradiance_error: error,
+            illuminance: illuminance,
+            illuminance_interval: (illuminance - margin, illuminance + margin),
+        }
+    }
+}
+
+// standard error of the mean from the sum of squares
+fn standard_error(mean: Density, square: Density, n: Density) -> Density {
+    if n > 1.0 {
+        let variance = (square - n * mean * mean) / (n - 1.0);
+        (variance.max(0.0) / n).sqrt()
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use super::super::beam::Frequency;
+    use super::super::beam::Material;
+    use super::super::primitive::Sphere;
+
+    use rand;
+
+    #[test]
+    fn inside_emitter() {
+        // uniform radiance from every direction gives pi times the radiance
+        let mut rng = rand::thread_rng();
+        let sky = Sphere::new(V3::new(0.0, 0.0, 0.0), 100.0, Material::emission(Beam::flat(1.0)));
+        let scene = Scene::new(vec![sky], vec![]);
+        let calibration = Calibration::new(2.0);
+        let patch = Patch::new(V3::new(0.0, 0.0, 0.0), V3::new(0.0, 0.0, 1.0));
+
+        let measurement = patch.measure(&scene, 64, &calibration, &mut rng);
+        let expected = calibration.luminance(&Beam::flat(1.0)) * M_PI;
+        assert!((measurement.illuminance - expected).abs() < 1.0e-6);
+        assert!(measurement.illuminance_interval.1 - measurement.illuminance_interval.0 < 1.0e-6);
+        for frequency in Frequency::all() {
+            assert!((measurement.irradiance.density(&frequency) - 2.0 * M_PI).abs() < 1.0e-9);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use super::beam::Density;
+use super::beam::Frequency;
+
+use rand::Rng;
+use rand::distributions::Sample;
+use rand::distributions::Range;
+
+/// Metropolis struct is the setting of the primary sample space Metropolis light transport,
+/// the random numbers the path is traced with are mutated, so the path near the found one
+/// is traced next, it helps with the light coming through small openings
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Metropolis {
+    bootstrap: usize,
+    large_step: Density,
+}
+
+impl Default for Metropolis {
+    fn default() -> Self {
+        Metropolis {
+            bootstrap: 10000,
+            large_step: 0.3,
+        }
+    }
+}
+
+impl Metropolis {
+    /// number of independent paths the brightness of the image is estimated from
+    /// and the chain is started from in every pass
+    pub fn with_bootstrap(self, bootstrap: usize) -> Self {
+        Metropolis {
+            bootstrap: bootstrap,
+            ..self
+        }
+    }
+
+    /// probability that the path is replaced by the independent one instead of mutated
+    pub fn with_large_step(self, large_step: Density) -> Self {
+        Metropolis {
+            large_step: large_step,
+            ..self
+        }
+    }
+
+    /// run the chain of `mutations`, the `path` traces the pixel and the frequency
+    /// from the random numbers and finds the radiance, the `splat` gets the weight of the pixel,
+    /// the sum of the weights is the expected radiance of all the mutations
+    pub(crate) fn run<P, S>(
+        &self,
+        mutations: usize,
+        mut rng: &mut dyn Rng,
+        mut path: P,
+        mut splat: S,
+    ) where
+        P: FnMut(&mut dyn Rng) -> (usize, Frequency, Density),
+        S: FnMut(usize, &Frequency, Density),
+    {
+        let mut trace = |current: &[Density], large: bool, rng: &mut dyn Rng| {
+            let mut sample = PrimarySample::new(current, large, rng);
+            let (pixel, frequency, radiance) = path(&mut sample);
+            State {
+                values: sample.values,
+                pixel: pixel,
+                frequency: frequency,
+                radiance: radiance,
+            }
+        };
+
+        let seeds = (0..self.bootstrap)
+            .map(|_| trace(&[], true, &mut rng))
+            .collect::<Vec<_>>();
+        let sum = seeds.iter().fold(0.0, |sum, seed| sum + seed.radiance);
+        if sum <= 0.0 {
+            return;
+        }
+
+        // the chain starts from the seed chosen proportionally to its radiance,
+        // the last bright one is taken when the rounding leaves the choice above all of them
+        let brightness = sum / (self.bootstrap as Density);
+        let mut choice = Range::new(0.0, sum).sample(&mut rng);
+        let mut bright = seeds.into_iter().filter(|seed| seed.radiance > 0.0);
+        let mut current = match bright.next() {
+            Some(seed) => seed,
+            None => return,
+        };
+        for seed in bright {
+            if choice < current.radiance {
+                break;
+            }
+            choice -= current.radiance;
+            current = seed;
+        }
+
+        for _ in 0..mutations {
+            let large = Range::new(0.0, 1.0).sample(&mut rng) < self.large_step;
+            let proposed = trace(&current.values, large, &mut rng);
+
+            // both states are recorded with their expected weights
+            let accept = (proposed.radiance / current.radiance).min(1.0);
+            if proposed.radiance > 0.0 {
+                splat(proposed.pixel, &proposed.frequency, accept * brightness);
+            }
+            splat(current.pixel, &current.frequency, (1.0 - accept) * brightness);
+
+            if Range::new(0.0, 1.0).sample(&mut rng) < accept {
+                current = proposed;
+            }
+        }
+    }
+}
+
+// state of the chain, the random numbers and the path traced from them
+struct State {
+    values: Vec<Density>,
+    pixel: usize,
+    frequency: Frequency,
+    radiance: Density,
+}
+
+// the random numbers of the path, the numbers not taken by the current path are new
+struct PrimarySample<'a> {
+    current: &'a [Density],
+    values: Vec<Density>,
+    large: bool,
+    rng: &'a mut dyn Rng,
+}
+
+impl<'a> PrimarySample<'a> {
+    fn new(current: &'a [Density], large: bool, rng: &'a mut dyn Rng) -> Self {
+        PrimarySample {
+            current: current,
+            values: Vec::with_capacity(current.len()),
+            large: large,
+            rng: rng,
+        }
+    }
+
+    fn next(&mut self) -> Density {
+        let index = self.values.len();
+        let value = if self.large || index >= self.current.len() {
+            Range::new(0.0, 1.0).sample(&mut self.rng)
+        } else {
+            // exponentially distributed step from 1/1024 to 1/64 in either direction
+            let (small, large): (Density, Density) = (1.0 / 1024.0, 1.0 / 64.0);
+            let exponent: Density = Range::new(0.0, 1.0).sample(&mut self.rng);
+            let step = large * (-(large / small).ln() * exponent).exp();
+            let value = if Range::new(0.0, 1.0).sample(&mut self.rng) < 0.5 {
+                self.current[index] + step
+            } else {
+                self.current[index] - step
+            };
+            value - value.floor()
+        };
+        self.values.push(value);
+        value
+    }
+}
+
+impl<'a> Rng for PrimarySample<'a> {
+    fn next_u32(&mut self) -> u32 {
+        (self.next() * 4294967296.0) as u32
+    }
+
+    // all the bits of the number are random, so the ranges of integers are uniform
+    fn next_u64(&mut self) -> u64 {
+        (self.next() * 9007199254740992.0) as u64
+    }
+
+    fn next_f32(&mut self) -> f32 {
+        (self.next() as f32).min(1.0 - f32::EPSILON)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        self.next()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use super::super::fixture;
+    use super::super::algebra::V3;
+    use super::super::scene::Method;
+    use super::super::screen::Eye;
+    use super::super::screen::Screen;
+    use super::super::screen::Size;
+    use super::super::screen::Image;
+
+    use rand;
+
+    #[test]
+    fn brightness() {
+        // the chain gives the same image as the independent paths
+        let mut rng = rand::thread_rng();
+        let scene = fixture::panel(Method::default());
+
+        let eye = Eye {
+            position: V3::new(3.0, 0.0, 0.3),
+            forward: V3::new(-1.0, 0.0, -0.1).normalize(),
+            right: V3::new(0.0, 1.0, 0.0),
+            up: V3::new(0.1, 0.0, 1.0).normalize(),
+            width: 1.0,
+            height: 1.0,
+            distance: 1.0,
+            polarizer: None,
+        };
+        let format = Size {
+            horizontal_count: 2,
+            vertical_count: 2,
+        };
+        let screen = Screen::new(format, eye);
+        let metropolis = Metropolis::default().with_bootstrap(1000);
+
+        let (mut path, mut chain) = (screen.create_image(), screen.create_image());
+        for _ in 0..500 {
+            screen.sample(&scene, &mut path, &mut rng);
+            screen.sample_metropolis(&scene, &mut chain, &metropolis, &mut rng);
+        }
+
+        let luminance =
+            |image: &Image| image.channels().iter().fold(0.0, |sum, pixel| sum + pixel[1]);
+        assert!((luminance(&path) - luminance(&chain)).abs() < 0.15 * luminance(&path));
+    }
+}
@@ -0,0 +1,87 @@
+use super::algebra::M;
+use super::algebra::M_PI;
+
+use super::beam::Beam;
+use super::beam::Density;
+
+use super::color;
+
+/// lumens per watt at the peak of the photopic luminous efficiency
+pub const LUMINOUS_EFFICACY: M = 683.0;
+
+/// Calibration struct relates the emission density to absolute units,
+/// the `radiance` is the spectral radiance in W/(sr m² nm) of the emission density one
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Calibration {
+    radiance: Density,
+}
+
+impl Calibration {
+    pub fn new(radiance: Density) -> Self {
+        Calibration { radiance: radiance }
+    }
+
+    pub fn radiance(&self) -> Density {
+        self.radiance
+    }
+
+    /// emission of the lambertian emitter of the `area` in square metres radiating
+    /// `watts` with the spectrum of the given shape
+    pub fn radiant(&self, shape: &Beam, watts: M, area: M) -> Beam {
+        let integral = shape.clone() * &widths();
+        self.emission(shape, watts / integral, area)
+    }
+
+    /// the same as `radiant` for the luminous flux in lumens
+    pub fn luminous(&self, shape: &Beam, lumens: M, area: M) -> Beam {
+        let integral = shape.clone() * &efficiency() * LUMINOUS_EFFICACY;
+        self.emission(shape, lumens / integral, area)
+    }
+
+    /// luminance in cd/m² of the spectrum given by emission densities
+    pub fn luminance(&self, beam: &Beam) -> Density {
+        beam.clone() * &efficiency() * LUMINOUS_EFFICACY * self.radiance
+    }
+
+    // the shape scaled by `scale` is the spectral flux in W/nm
+    fn emission(&self, shape: &Beam, scale: M, area: M) -> Beam {
+        shape.clone() * (scale / (M_PI * area * self.radiance))
+    }
+}
+
+// bandwidths in nanometres
+fn widths() -> Beam {
+    Beam::from_bands(|frequency| {
+        let (shortest, longest) = frequency.bounds();
+        longest - shortest
+    })
+}
+
+// photopic luminous efficiency averaged over the bands multiplied by the bandwidths
+fn efficiency() -> Beam {
+    let average = Beam::from_function(color::luminosity);
+    Beam::from_bands(|frequency| {
+        let (shortest, longest) = frequency.bounds();
+        average.density(frequency) * (longest - shortest)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lambertian() {
+        let calibration = Calibration::new(10.0);
+        let emission = calibration.luminous(&Beam::d65(), 1000.0, 1.0);
+        assert!((calibration.luminance(&emission) - 1000.0 / M_PI).abs() < 1.0e-6);
+
+        let emission = calibration.radiant(&Beam::flat(1.0), 100.0, 2.0);
+        let watts = (emission * &widths()) * calibration.radiance() * M_PI * 2.0;
+        assert!((watts - 100.0).abs() < 1.0e-6);
+
+        // the bright emitter has the densities above one
+        let emission = calibration.luminous(&Beam::d65(), 1.0e6, 1.0e-4);
+        assert!((calibration.luminance(&emission) - 1.0e10 / M_PI).abs() < 1.0);
+    }
+}
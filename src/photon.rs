@@ -0,0 +1,329 @@
+use super::algebra::V3;
+use super::algebra::M;
+use super::algebra::M_PI;
+
+use super::beam::Density;
+use super::beam::Frequency;
+
+use super::bsdf::Bsdf;
+
+use super::ray::Ray;
+use super::ray::PhotonicRay;
+use super::ray::GeometricalRay;
+
+use super::scene::Scene;
+use super::screen::Screen;
+
+use super::bidirectional;
+
+use super::integrator::Depth;
+use super::integrator::Integrator;
+use super::integrator::PathTracing;
+
+use super::spectrum::Spectrum;
+
+use std::cmp::Ordering;
+
+use rand::Rng;
+use rand::distributions::Sample;
+use rand::distributions::Range;
+
+/// PhotonMapping struct is the setting of the progressive photon mapping,
+/// every pass shoots new photons and gathers them in the smaller radius
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PhotonMapping {
+    photons: usize,
+    radius: M,
+    alpha: Density,
+    #[serde(default)]
+    depth: Depth,
+}
+
+impl PhotonMapping {
+    /// `photons` shot in every pass, `radius` of the gathering in the first pass
+    pub fn new(photons: usize, radius: M) -> Self {
+        PhotonMapping {
+            photons: photons,
+            radius: radius,
+            alpha: 2.0 / 3.0,
+            depth: Depth::default(),
+        }
+    }
+
+    /// fraction of the photons kept in the next pass, between 0 and 1,
+    /// the smaller one reduces the bias faster and the noise slower
+    pub fn with_alpha(self, alpha: Density) -> Self {
+        PhotonMapping {
+            alpha: alpha,
+            ..self
+        }
+    }
+
+    /// the same depth limits the photons and the rays from the eye
+    pub fn with_depth(self, depth: Depth) -> Self {
+        PhotonMapping {
+            depth: depth,
+            ..self
+        }
+    }
+
+    // radius of the pass numbered from zero
+    fn radius(&self, pass: usize) -> M {
+        let square = (1..(pass + 1)).fold(self.radius * self.radius, |square, i| {
+            let i = i as Density;
+            square * (i + self.alpha) / (i + 1.0)
+        });
+        square.sqrt()
+    }
+}
+
+// the photon arriving at the surface, the power is already divided by the number of photons
+struct Photon {
+    position: V3,
+    direction: V3,
+    frequency: Frequency,
+    power: Density,
+}
+
+/// the pass shoots its own photons, without the pass the ray is path traced
+impl Integrator for PhotonMapping {
+    fn pass(
+        &self,
+        scene: &Scene,
+        _: Option<&Screen>,
+        pass: usize,
+        rng: &mut dyn Rng,
+    ) -> Option<Box<dyn Integrator>> {
+        Some(Box::new(PhotonPass {
+            mapping: self.clone(),
+            map: PhotonMap::new(scene, self, pass, rng),
+        }))
+    }
+
+    fn trace(&self, scene: &Scene, ray: &Ray, rng: &mut dyn Rng) -> Density {
+        // a photon map for the single ray would cost the whole pass
+        let path = PathTracing::default().with_depth(self.depth.clone());
+        path.trace(scene, ray, rng)
+    }
+}
+
+// photons of one pass along with the setting they are gathered with
+struct PhotonPass {
+    mapping: PhotonMapping,
+    map: PhotonMap,
+}
+
+impl Integrator for PhotonPass {
+    fn trace(&self, scene: &Scene, ray: &Ray, rng: &mut dyn Rng) -> Density {
+        trace(scene, &self.mapping, &self.map, ray, rng)
+    }
+}
+
+// the kd-tree of the photons shot in one pass, the median photon
+// of every range splits it along the axis of the node
+struct PhotonMap {
+    photons: Vec<Photon>,
+    axes: Vec<usize>,
+    radius: M,
+}
+
+impl PhotonMap {
+    fn new(
+        scene: &Scene,
+        mapping: &PhotonMapping,
+        pass: usize,
+        mut rng: &mut dyn Rng,
+    ) -> Self {
+        let bands = Spectrum::current().size();
+        let scale = (bands as Density) / (mapping.photons.max(1) as Density);
+
+        let mut photons = Vec::with_capacity(mapping.photons);
+        for _ in 0..mapping.photons {
+            let frequency = Frequency::new(Range::new(0, bands).sample(&mut rng));
+            let path = bidirectional::light_subpath(scene, &mapping.depth, &frequency, &mut rng);
+            for (previous, vertex) in path.iter().zip(path.iter().skip(1)) {
+                photons.push(Photon {
+                    position: vertex.vertex.position,
+                    direction: (vertex.vertex.position - previous.vertex.position).normalize(),
+                    frequency: frequency.clone(),
+                    power: vertex.throughput * scale,
+                });
+            }
+        }
+
+        let mut axes = vec![0; photons.len()];
+        build(&mut photons, &mut axes);
+
+        PhotonMap {
+            photons: photons,
+            axes: axes,
+            radius: mapping.radius(pass),
+        }
+    }
+
+    // radiance scattered by the non-singular components of the bsdf toward the traced ray
+    fn estimate(&self, bsdf: &dyn Bsdf, ray: &Ray, position: V3, normal: V3) -> Density {
+        let (incident, frequency) = (ray.direction(), ray.frequency());
+        let normal = bidirectional::facing(normal.normalize(), incident);
+
+        let mut sum = 0.0;
+        self.gather(0, self.photons.len(), position, &mut |photon| {
+            if photon.frequency == frequency {
+                let outgoing = -photon.direction;
+                let value = bsdf.evaluate(incident, outgoing, normal, &frequency);
+                let cosine = (normal * outgoing).abs();
+                if value > 0.0 && cosine > 0.0 {
+                    sum += value / cosine * photon.power;
+                }
+            }
+        });
+
+        sum / (M_PI * self.radius * self.radius)
+    }
+
+    // visit the photons of the range closer to the position than the radius
+    fn gather<F>(&self, begin: usize, end: usize, position: V3, visit: &mut F)
+    where
+        F: FnMut(&Photon),
+    {
+        if begin >= end {
+            return;
+        }
+
+        let middle = begin + (end - begin) / 2;
+        let photon = &self.photons[middle];
+        let offset = position - photon.position;
+        if offset * offset < self.radius * self.radius {
+            visit(photon);
+        }
+
+        let axis = self.axes[middle];
+        let distance = component(position, axis) - component(photon.position, axis);
+        if distance < self.radius {
+            self.gather(begin, middle, position, visit);
+        }
+        if distance > -self.radius {
+            self.gather(middle + 1, end, position, visit);
+        }
+    }
+}
+
+// order the photons so the median of every range splits it along its widest axis
+fn build(photons: &mut [Photon], axes: &mut [usize]) {
+    if photons.is_empty() {
+        return;
+    }
+
+    let (lower, upper) = photons.iter().fold(
+        ([M::INFINITY; 3], [M::NEG_INFINITY; 3]),
+        |(mut lower, mut upper), photon| {
+            for axis in 0..3 {
+                let value = component(photon.position, axis);
+                lower[axis] = lower[axis].min(value);
+                upper[axis] = upper[axis].max(value);
+            }
+            (lower, upper)
+        },
+    );
+    let axis = (0..3)
+        .max_by(|&a, &b| {
+            let (a, b) = (upper[a] - lower[a], upper[b] - lower[b]);
+            a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+        })
+        .unwrap_or(0);
+
+    let middle = photons.len() / 2;
+    photons.select_nth_unstable_by(middle, |a, b| {
+        let (a, b) = (component(a.position, axis), component(b.position, axis));
+        a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+    });
+    axes[middle] = axis;
+
+    let (photons_lower, photons_upper) = photons.split_at_mut(middle);
+    let (axes_lower, axes_upper) = axes.split_at_mut(middle);
+    build(photons_lower, axes_lower);
+    build(&mut photons_upper[1..], &mut axes_upper[1..]);
+}
+
+fn component(v: V3, axis: usize) -> M {
+    match axis {
+        0 => v.x(),
+        1 => v.y(),
+        _ => v.z(),
+    }
+}
+
+// progressive photon mapping, the ray goes through the singular scatterings
+// and the rest of the bsdf is estimated from the photons near the hit
+fn trace(
+    scene: &Scene,
+    mapping: &PhotonMapping,
+    map: &PhotonMap,
+    ray: &Ray,
+    mut rng: &mut dyn Rng,
+) -> Density {
+    let depth = &mapping.depth;
+    let mut ray = ray.clone();
+    let mut radiance = 0.0;
+    let mut throughput = 1.0;
+
+    for level in 0..depth.maximal() {
+        let result = match scene.intersect(&ray) {
+            Some(result) => result,
+            None => break,
+        };
+
+        let bsdf = result.surface.bsdf();
+        let emission = bsdf.emission(&ray.frequency());
+        let estimate = map.estimate(bsdf, &ray, result.position, result.normal);
+        radiance += (emission + estimate) * throughput;
+
+        // the non-singular scattering is already estimated
+        match bsdf.sample(&ray, result.position, result.normal, &mut rng) {
+            Some(ref scattering) if !scattering.singular => break,
+            Some(scattering) => {
+                match depth.survive(level, throughput * scattering.weight, &mut rng) {
+                    Some(survived) => throughput = survived,
+                    None => break,
+                }
+                ray = scattering.ray;
+            }
+            None => break,
+        }
+    }
+
+    radiance
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use super::super::fixture;
+    use super::super::scene::Method;
+
+    use rand;
+
+    #[test]
+    fn floor() {
+        // the photons under the lamp give the same answer as the path tracing
+        let mut rng = rand::thread_rng();
+        let direction = V3::new(-1.0, 0.0, -1.0).normalize();
+        let ray = Ray::new(V3::new(0.2, 0.0, 0.2), direction, Frequency::new(0));
+
+        let samples = 20000;
+        let path = fixture::panel(Method::PathTracing(PathTracing::default()));
+        let sum = (0..samples).fold(0.0, |sum, _| sum + path.trace(&ray, &mut rng));
+        let path = sum / (samples as Density);
+
+        let (passes, samples) = (40, 250);
+        let photon = fixture::panel(Method::PhotonMapping(PhotonMapping::new(20000, 0.3)));
+        let sum = (0..passes).fold(0.0, |sum, pass| {
+            let integrator = photon.method().pass(&photon, None, pass, &mut rng).unwrap();
+            (0..samples).fold(sum, |sum, _| sum + integrator.trace(&photon, &ray, &mut rng))
+        });
+        let photon = sum / ((passes * samples) as Density);
+
+        assert!((path - photon).abs() < 0.1 * path);
+    }
+}
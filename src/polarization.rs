@@ -0,0 +1,350 @@
+use std::ops::Mul;
+
+use super::algebra::V3;
+use super::algebra::M;
+
+use super::beam::Density;
+use super::beam::Factor;
+
+/// Stokes struct is a polarization state (I, Q, U, V) relative to some reference axis,
+/// rays are traced from the eye, so they carry the Stokes vector of the detector
+/// which is multiplied by Mueller matrices from the left
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct Stokes {
+    i: M,
+    q: M,
+    u: M,
+    v: M,
+}
+
+impl Stokes {
+    pub fn new(i: M, q: M, u: M, v: M) -> Self {
+        Stokes { i: i, q: q, u: u, v: v }
+    }
+
+    pub fn unpolarized() -> Self {
+        Self::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    pub fn intensity(&self) -> M {
+        self.i
+    }
+
+    /// scale the vector so the intensity is one, return the former intensity
+    pub fn normalize(self) -> (Self, Density) {
+        let i = self.i;
+        if i > 0.0 {
+            (Self::new(1.0, self.q / i, self.u / i, self.v / i), i)
+        } else {
+            (Self::unpolarized(), 0.0)
+        }
+    }
+}
+
+/// Mueller struct is a matrix acting on Stokes vectors
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Mueller {
+    m: [[M; 4]; 4],
+}
+
+impl Mueller {
+    pub fn new(m: [[M; 4]; 4]) -> Self {
+        Mueller { m: m }
+    }
+
+    pub fn identity() -> Self {
+        Self::diagonal(1.0, 1.0, 1.0)
+    }
+
+    /// ideal depolarizer like a lambertian surface
+    pub fn depolarizer() -> Self {
+        Self::diagonal(0.0, 0.0, 0.0)
+    }
+
+    /// ideal linear polarizer transmitting along the reference axis rotated by `angle`
+    pub fn linear_polarizer(angle: M) -> Self {
+        let polarizer = Self::new([
+            [0.5, 0.5, 0.0, 0.0],
+            [0.5, 0.5, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 0.0],
+        ]);
+        polarizer.rotate(angle)
+    }
+
+    /// linear retarder (wave plate) with fast axis rotated by `angle`,
+    /// the `retardance` is the phase difference in radians
+    pub fn retarder(angle: M, retardance: M) -> Self {
+        let (c, s) = (retardance.cos(), retardance.sin());
+        let retarder = Self::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, c, s],
+            [0.0, 0.0, -s, c],
+        ]);
+        retarder.rotate(angle)
+    }
+
+    /// the reference axis is perpendicular to the plane of incidence,
+    /// the `factor` is the same refraction coefficient as in `Ray::refract`
+    pub fn fresnel_reflection(cosine: M, factor: Factor) -> Self {
+        match Fresnel::new(cosine, factor) {
+            Fresnel::Partial { rs: rs, rp: rp } => {
+                Self::interface(rs * rs, rp * rp, rs * rp, 0.0)
+            }
+            Fresnel::Total { delta: delta } => Self::interface(1.0, 1.0, delta.cos(), delta.sin()),
+        }
+    }
+
+    /// the reference axis is perpendicular to the plane of incidence,
+    /// the `factor` is the same refraction coefficient as in `Ray::refract`
+    pub fn fresnel_transmission(cosine: M, factor: Factor) -> Self {
+        match Fresnel::new(cosine, factor) {
+            Fresnel::Partial { rs: rs, rp: rp } => {
+                let (ts, tp) = (1.0 - rs * rs, 1.0 - rp * rp);
+                Self::interface(ts, tp, (ts * tp).sqrt(), 0.0)
+            }
+            Fresnel::Total { .. } => Self::interface(0.0, 0.0, 0.0, 0.0),
+        }
+    }
+
+    fn diagonal(q: M, u: M, v: M) -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, q, 0.0, 0.0],
+            [0.0, 0.0, u, 0.0],
+            [0.0, 0.0, 0.0, v],
+        ])
+    }
+
+    fn interface(s: M, p: M, cross: M, phase: M) -> Self {
+        Self::new([
+            [(s + p) / 2.0, (s - p) / 2.0, 0.0, 0.0],
+            [(s - p) / 2.0, (s + p) / 2.0, 0.0, 0.0],
+            [0.0, 0.0, cross, phase],
+            [0.0, 0.0, -phase, cross],
+        ])
+    }
+
+    /// rotation of the reference axis by `angle`
+    pub fn rotation(angle: M) -> Self {
+        let (c, s) = ((2.0 * angle).cos(), (2.0 * angle).sin());
+        Self::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, c, s, 0.0],
+            [0.0, -s, c, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// the same element rotated by `angle` around the ray
+    pub fn rotate(&self, angle: M) -> Self {
+        &(&Self::rotation(-angle) * self) * &Self::rotation(angle)
+    }
+}
+
+impl<'a> Mul<&'a Mueller> for &Mueller {
+    type Output = Mueller;
+
+    fn mul(self, rhs: &'a Mueller) -> Self::Output {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, element) in row.iter_mut().enumerate() {
+                *element = (0..4).fold(0.0, |sum, k| sum + self.m[i][k] * rhs.m[k][j]);
+            }
+        }
+
+        Mueller { m: m }
+    }
+}
+
+impl<'a> Mul<&'a Mueller> for Stokes {
+    type Output = Stokes;
+
+    fn mul(self, rhs: &'a Mueller) -> Self::Output {
+        let d = [self.i, self.q, self.u, self.v];
+        let column = |j: usize| (0..4).fold(0.0, |sum, k| sum + d[k] * rhs.m[k][j]);
+        Stokes::new(column(0), column(1), column(2), column(3))
+    }
+}
+
+/// PolarizingFilter struct is an optical element which axis is fixed in the scene
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PolarizingFilter {
+    axis: V3,
+    mueller: Mueller,
+}
+
+impl PolarizingFilter {
+    pub fn new(axis: V3, mueller: Mueller) -> Self {
+        PolarizingFilter {
+            axis: axis,
+            mueller: mueller,
+        }
+    }
+
+    pub fn linear(axis: V3) -> Self {
+        Self::new(axis, Mueller::linear_polarizer(0.0))
+    }
+
+    /// birefringent plate with the fast axis along `axis`
+    pub fn retarder(axis: V3, retardance: M) -> Self {
+        Self::new(axis, Mueller::retarder(0.0, retardance))
+    }
+
+    pub fn axis(&self) -> V3 {
+        self.axis
+    }
+
+    pub fn mueller(&self) -> &Mueller {
+        &self.mueller
+    }
+}
+
+enum Fresnel {
+    Partial { rs: M, rp: M },
+    Total { delta: M },
+}
+
+impl Fresnel {
+    fn new(cosine: M, factor: Factor) -> Self {
+        let n = 1.0 / factor;
+        let sin2 = 1.0 - cosine * cosine;
+        let cos2 = 1.0 - sin2 * factor * factor;
+        if cos2 >= 0.0 {
+            let c = cos2.sqrt();
+            Fresnel::Partial {
+                rs: (cosine - n * c) / (cosine + n * c),
+                rp: (n * cosine - c) / (n * cosine + c),
+            }
+        } else {
+            // phase difference between p and s components after total internal reflection
+            let root = (sin2 - n * n).sqrt();
+            let delta_s = 2.0 * (root / cosine).atan();
+            let delta_p = 2.0 * (root / (n * n * cosine)).atan();
+            Fresnel::Total { delta: delta_p - delta_s }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::algebra::M_PI;
+    use super::super::beam::Beam;
+    use super::super::beam::BeamRefract;
+    use super::super::beam::Frequency;
+    use super::super::beam::Material;
+    use super::super::bsdf::Bsdf;
+    use super::super::ray::Ray;
+    use super::super::ray::GeometricalRay;
+
+    use rand;
+    use rand::Rng;
+
+    #[test]
+    fn brewster() {
+        let n: Factor = 1.5;
+        let cosine = (1.0 / (1.0 + n * n)).sqrt();
+        let reflection = Mueller::fresnel_reflection(cosine, 1.0 / n);
+        let transmission = Mueller::fresnel_transmission(cosine, 1.0 / n);
+
+        // at the brewster angle only s-polarized light is reflected
+        let p = Stokes::new(1.0, -1.0, 0.0, 0.0);
+        assert!((p * &reflection).intensity().abs() < 1.0e-9);
+
+        // energy is conserved for any polarization
+        let d = Stokes::new(1.0, 0.3, -0.4, 0.2);
+        let total = (d * &reflection).intensity() + (d * &transmission).intensity();
+        assert!((total - 1.0).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn glass() {
+        // the refracting material reflects the part of the ray the fresnel equations give
+        let mut rng = rand::thread_rng();
+        let n: Factor = 1.5;
+        let glass = Material::refraction(Beam::flat(1.0), BeamRefract::from_band_index(|_| n));
+        let cosine = (1.0 / (1.0 + n * n)).sqrt();
+        let direction = V3::new((1.0 - cosine * cosine).sqrt(), 0.0, -cosine);
+        let ray = Ray::new(V3::new(0.0, 0.0, 1.0), direction, Frequency::new(0));
+        let normal = V3::new(0.0, 0.0, 1.0);
+
+        let samples = 20000;
+        let reflected = (0..samples).fold(0, |count, _| {
+            let scattering = glass.sample(&ray, V3::new(0.0, 0.0, 0.0), normal, &mut rng);
+            match scattering {
+                Some(ref scattering) if scattering.ray.direction() * normal > 0.0 => count + 1,
+                _ => count,
+            }
+        });
+
+        // only the s-polarized half of the light is reflected at the brewster angle
+        let s = Stokes::new(1.0, 1.0, 0.0, 0.0) * &Mueller::fresnel_reflection(cosine, 1.0 / n);
+        let expected = s.intensity() / 2.0;
+        assert!(((reflected as Factor) / (samples as Factor) - expected).abs() < 0.01);
+    }
+
+    #[test]
+    fn slab() {
+        // the ray leaving the glass is refracted back to its direction
+        let mut rng = rand::thread_rng();
+        let glass = Material::refraction(Beam::flat(1.0), BeamRefract::from_band_index(|_| 1.5));
+        let direction = V3::new(0.6, 0.0, -0.8);
+        let normal = V3::new(0.0, 0.0, 1.0);
+        let refracted = |ray: &Ray, rng: &mut dyn Rng| loop {
+            let scattering = glass.sample(ray, V3::new(0.0, 0.0, 0.0), normal, rng);
+            match scattering {
+                Some(ref scattering) if scattering.ray.direction() * normal < 0.0 => {
+                    return scattering.ray.clone()
+                }
+                _ => (),
+            }
+        };
+
+        let ray = Ray::new(V3::new(0.0, 0.0, 1.0), direction, Frequency::new(0));
+        let inside = refracted(&ray, &mut rng);
+        assert!(inside.inside());
+        assert!((inside.direction().x() - 0.4).abs() < 1.0e-9);
+
+        let outside = refracted(&inside, &mut rng);
+        assert!(!outside.inside());
+        assert!((outside.direction() - direction).length() < 1.0e-9);
+    }
+
+    #[test]
+    fn sheet() {
+        // the polarizer in front of the slab doesn't change the way the slab bends the ray
+        let mut rng = rand::thread_rng();
+        let glass = Material::refraction(Beam::flat(1.0), BeamRefract::from_band_index(|_| 1.5));
+        let sheet = Material::filter(PolarizingFilter::linear(V3::new(0.0, 1.0, 0.0)));
+        let direction = V3::new(0.8, 0.0, -0.6);
+        let normal = V3::new(0.0, 0.0, 1.0);
+        let refracted = |ray: &Ray, rng: &mut dyn Rng| {
+            (0..1000)
+                .filter_map(|_| glass.sample(ray, V3::new(0.0, 0.0, 0.0), normal, rng))
+                .map(|scattering| scattering.ray)
+                .find(|ray| ray.direction() * normal < 0.0)
+                .expect("the slab never refracts the ray")
+        };
+
+        let ray = Ray::new(V3::new(0.0, 0.0, 2.0), direction, Frequency::new(0));
+        let slab = refracted(&refracted(&ray, &mut rng), &mut rng);
+
+        let ray = sheet.sample(&ray, V3::new(0.0, 0.0, 1.0), normal, &mut rng).unwrap().ray;
+        assert!(!ray.inside());
+        assert!((ray.direction() - direction).length() < 1.0e-9);
+        let filtered = refracted(&refracted(&ray, &mut rng), &mut rng);
+        assert!((filtered.direction() - slab.direction()).length() < 1.0e-9);
+        assert!(!filtered.inside());
+    }
+
+    #[test]
+    fn crossed_polarizers() {
+        let a = Mueller::linear_polarizer(0.0);
+        let b = Mueller::linear_polarizer(M_PI / 2.0);
+        let d = Stokes::unpolarized() * &a;
+        assert!(((d * &b).intensity()).abs() < 1.0e-9);
+        assert!(((d * &a).intensity() - 0.5).abs() < 1.0e-9);
+    }
+}
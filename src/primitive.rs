@@ -1,18 +1,26 @@
 use super::algebra::V3;
 use super::algebra::M;
 use super::algebra::M_INFINITY;
+use super::algebra::M_PI;
 
-use super::beam::Material;
+use super::beam::Density;
+use super::beam::Frequency;
+
+use super::bsdf::Surface;
 
 use super::ray::Ray;
 use super::ray::GeometricalRay;
 
 use std::cmp::Ordering;
 
-pub struct IntersectResult {
+use rand::Rng;
+use rand::distributions::Sample;
+use rand::distributions::Range;
+
+pub struct IntersectResult<'a> {
     pub position: V3,
     pub normal: V3,
-    pub material: Material,
+    pub surface: &'a Surface,
 }
 
 pub struct IntersectInfo {
@@ -45,22 +53,38 @@ impl PartialOrd for IntersectInfo {
 
 pub trait Primitive {
     fn intersect(&self, ray: &Ray) -> Option<IntersectInfo>;
-    fn result(&self, ray: &Ray, info: IntersectInfo) -> IntersectResult;
+    fn result(&self, ray: &Ray, info: IntersectInfo) -> IntersectResult<'_>;
+    fn surface(&self) -> &Surface;
+
+    /// direction from the `origin` toward a random point of the primitive
+    /// along with the probability density per solid angle
+    fn sample_direction(&self, origin: V3, rng: &mut dyn Rng) -> Option<(V3, Density)>;
+
+    /// probability density per solid angle of the direction chosen by `sample_direction`
+    fn pdf(&self, origin: V3, direction: V3) -> Density;
+
+    /// random point uniformly distributed over the surface along with the unit normal
+    fn sample_point(&self, rng: &mut dyn Rng) -> (V3, V3);
+
+    fn area(&self) -> M;
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Sphere {
     center: V3,
     radius: M,
-    material: Material,
+    surface: Surface,
 }
 
 impl Sphere {
-    pub fn new(center: V3, radius: M, material: Material) -> Self {
+    pub fn new<S>(center: V3, radius: M, surface: S) -> Self
+    where
+        S: Into<Surface>,
+    {
         Sphere {
             center: center,
             radius: radius,
-            material: material,
+            surface: surface.into(),
         }
     }
 }
@@ -91,16 +115,66 @@ impl Primitive for Sphere {
             }
         };
 
-        distance.map(|t| (IntersectInfo { distance: t, r: r, normal: None }))
+        distance.map(|t| IntersectInfo { distance: t, r: r, normal: None })
     }
 
-    fn result(&self, ray: &Ray, info: IntersectInfo) -> IntersectResult {
+    fn result(&self, ray: &Ray, info: IntersectInfo) -> IntersectResult<'_> {
         let position = ray.position() + ray.direction() * info.distance;
         let normal = info.normal.unwrap_or((position - self.center) / info.r);
         IntersectResult {
             position: position,
             normal: normal,
-            material: self.material.clone(),
+            surface: &self.surface,
+        }
+    }
+
+    fn surface(&self) -> &Surface {
+        &self.surface
+    }
+
+    fn sample_point(&self, mut rng: &mut dyn Rng) -> (V3, V3) {
+        let a = Range::new(0.0, M_PI * 2.0).sample(&mut rng);
+        let z: M = Range::new(-1.0, 1.0).sample(&mut rng);
+        let r = (1.0 - z * z).sqrt();
+        let normal = V3::new(r * a.cos(), r * a.sin(), z);
+        (self.center + normal * self.radius, normal)
+    }
+
+    fn area(&self) -> M {
+        4.0 * M_PI * self.radius * self.radius
+    }
+
+    // uniform in the cone of directions the sphere is seen in
+    fn sample_direction(&self, origin: V3, mut rng: &mut dyn Rng) -> Option<(V3, Density)> {
+        let q = self.center - origin;
+        let distance2 = q * q;
+        let (axis, cosine_max) = if distance2 > self.radius * self.radius {
+            (q.normalize(), (1.0 - self.radius * self.radius / distance2).sqrt())
+        } else {
+            (V3::new(0.0, 0.0, 1.0), -1.0)
+        };
+
+        let a = Range::new(0.0, M_PI * 2.0).sample(&mut rng);
+        let cosine: M = 1.0 - Range::new(0.0, 1.0).sample(&mut rng) * (1.0 - cosine_max);
+        let sine = (1.0 - cosine * cosine).max(0.0).sqrt();
+        let (tangent, bitangent) = axis.basis();
+        let direction = tangent * (sine * a.cos()) + bitangent * (sine * a.sin()) + axis * cosine;
+
+        Some((direction, 1.0 / (2.0 * M_PI * (1.0 - cosine_max))))
+    }
+
+    fn pdf(&self, origin: V3, direction: V3) -> Density {
+        let q = self.center - origin;
+        let distance2 = q * q;
+        if distance2 > self.radius * self.radius {
+            let cosine_max = (1.0 - self.radius * self.radius / distance2).sqrt();
+            if direction * q.normalize() >= cosine_max {
+                1.0 / (2.0 * M_PI * (1.0 - cosine_max))
+            } else {
+                0.0
+            }
+        } else {
+            1.0 / (4.0 * M_PI)
         }
     }
 }
@@ -110,16 +184,19 @@ pub struct Triangle {
     a: V3,
     b: V3,
     c: V3,
-    material: Material,
+    surface: Surface,
 }
 
 impl Triangle {
-    pub fn new(a: V3, b: V3, c: V3, material: Material) -> Self {
+    pub fn new<S>(a: V3, b: V3, c: V3, surface: S) -> Self
+    where
+        S: Into<Surface>,
+    {
         Triangle {
             a: a,
             b: b,
             c: c,
-            material: material,
+            surface: surface.into(),
         }
     }
 }
@@ -146,23 +223,83 @@ impl Primitive for Triangle {
             let r = if cw { 1.0 } else { -1.0 };
             // scaled outer normal
             let normal = (pc - pa).cross(pb - pa);
-            Some(IntersectInfo {
-                // it is not necessary to normalize the normal
-                distance: (pa * normal) / (ray.direction() * normal),
-                r: r,
-                normal: Some(normal)
-            })
+            // it is not necessary to normalize the normal
+            let distance = (pa * normal) / (ray.direction() * normal);
+            if distance >= 0.0 {
+                Some(IntersectInfo {
+                    distance: distance,
+                    r: r,
+                    normal: Some(normal)
+                })
+            } else {
+                // the triangle is behind the ray
+                None
+            }
         } else {
             None
         }
     }
 
-    fn result(&self, ray: &Ray, info: IntersectInfo) -> IntersectResult {
+    fn result(&self, ray: &Ray, info: IntersectInfo) -> IntersectResult<'_> {
         let position = ray.position() + ray.direction() * info.distance;
+        let normal = info.normal.unwrap_or((self.c - self.a).cross(self.b - self.a).normalize());
+
+        // the normal faces the incident ray whatever the order of the vertices is
+        let normal = if normal * ray.direction() > 0.0 {
+            normal * (-1.0)
+        } else {
+            normal
+        };
+
         IntersectResult {
             position: position,
-            normal: info.normal.unwrap_or((self.c - self.a).cross(self.b - self.a).normalize()),
-            material: self.material.clone(),
+            normal: normal,
+            surface: &self.surface,
+        }
+    }
+
+    fn surface(&self) -> &Surface {
+        &self.surface
+    }
+
+    fn sample_point(&self, mut rng: &mut dyn Rng) -> (V3, V3) {
+        let s: M = Range::new(0.0, 1.0).sample(&mut rng);
+        let t: M = Range::new(0.0, 1.0).sample(&mut rng);
+        let (u, v) = (1.0 - s.sqrt(), t * s.sqrt());
+        let point = self.a + (self.b - self.a) * u + (self.c - self.a) * v;
+        (point, (self.c - self.a).cross(self.b - self.a).normalize())
+    }
+
+    fn area(&self) -> M {
+        (self.c - self.a).cross(self.b - self.a).length() / 2.0
+    }
+
+    // uniform over the area
+    fn sample_direction(&self, origin: V3, rng: &mut dyn Rng) -> Option<(V3, Density)> {
+        let (point, _) = self.sample_point(rng);
+        let direction = (point - origin).normalize();
+        let pdf = self.pdf(origin, direction);
+        if pdf > 0.0 {
+            Some((direction, pdf))
+        } else {
+            None
+        }
+    }
+
+    fn pdf(&self, origin: V3, direction: V3) -> Density {
+        let ray = Ray::new(origin, direction, Frequency::new(0));
+        match self.intersect(&ray) {
+            Some(info) => {
+                let normal = (self.c - self.a).cross(self.b - self.a);
+                let area = normal.length() / 2.0;
+                let cosine = (direction * normal.normalize()).abs();
+                if info.distance > 0.0 && cosine > 1.0e-9 {
+                    info.distance * info.distance / (area * cosine)
+                } else {
+                    0.0
+                }
+            }
+            None => 0.0,
         }
     }
 }
@@ -4,6 +4,10 @@ use super::algebra::M_PI;
 
 use super::beam::Frequency;
 use super::beam::Factor;
+use super::beam::Density;
+
+use super::polarization::Stokes;
+use super::polarization::Mueller;
 
 use rand::Rng;
 use rand::distributions::Sample;
@@ -14,6 +18,9 @@ pub struct Ray {
     position: V3,
     direction: V3,
     frequency: Frequency,
+    polarization: Stokes,
+    reference: V3,
+    inside: bool,
 }
 
 impl Ray {
@@ -24,21 +31,62 @@ impl Ray {
             position: position,
             direction: direction,
             frequency: frequency,
+            polarization: Stokes::unpolarized(),
+            reference: orthogonal(V3::new(1.0, 0.0, 0.0), direction),
+            inside: false,
         }
     }
 }
 
+/// direction around the unit normal distributed with density cosine over pi
+pub fn cosine_direction(normal: V3, mut rng: &mut dyn Rng) -> V3 {
+    let a = Range::new(0.0, M_PI * 2.0).sample(&mut rng);
+    let r2: M = Range::new(0.0, 1.0).sample(&mut rng);
+    let r = r2.sqrt();
+
+    let (tangent, bitangent) = normal.basis();
+
+    tangent * (r * a.cos()) + bitangent * (r * a.sin()) + normal * (1.0 - r2).sqrt()
+}
+
+/// unit vector perpendicular to the `direction` closest to the `axis`
+fn orthogonal(axis: V3, direction: V3) -> V3 {
+    let direction = direction.normalize();
+    let projection = axis - direction * (axis * direction);
+    if projection.length() > 1.0e-6 {
+        projection.normalize()
+    } else if direction * V3::new(0.0, 1.0, 0.0) < 0.9 {
+        orthogonal(V3::new(0.0, 1.0, 0.0), direction)
+    } else {
+        orthogonal(V3::new(0.0, 0.0, 1.0), direction)
+    }
+}
+
 pub trait PhotonicRay {
     fn frequency(&self) -> Frequency;
+
+    fn shift(&self, frequency: Frequency) -> Self;
+}
+
+pub trait PolarizedRay: Sized {
+    /// rotate the reference axis toward the `axis`
+    fn align(&self, axis: V3) -> Self;
+
+    /// pass through the optical element, return the probability to survive
+    fn filter(&self, mueller: &Mueller) -> (Self, Density);
 }
 
 pub trait GeometricalRay {
     fn position(&self) -> V3;
     fn direction(&self) -> V3;
 
-    fn diffuse(&self, position: V3, normal: V3, rng: &mut Rng) -> Self;
+    fn diffuse(&self, position: V3, normal: V3, rng: &mut dyn Rng) -> Self;
     fn reflect(&self, position: V3, normal: V3) -> Self;
     fn refract(&self, position: V3, normal: V3, factor: Factor) -> Self;
+    fn redirect(&self, position: V3, direction: V3) -> Self;
+
+    /// the ray is refracted into the medium and not out of it yet
+    fn inside(&self) -> bool;
 }
 
 impl GeometricalRay for Ray {
@@ -48,8 +96,11 @@ impl GeometricalRay for Ray {
     fn direction(&self) -> V3 {
         self.direction
     }
+    fn inside(&self) -> bool {
+        self.inside
+    }
 
-    fn diffuse(&self, position: V3, normal: V3, mut rng: &mut Rng) -> Self {
+    fn diffuse(&self, position: V3, normal: V3, mut rng: &mut dyn Rng) -> Self {
         let a = Range::new(0.0, M_PI * 2.0).sample(&mut rng);
         let z = Range::new(-1.0, 1.0).sample(&mut rng);
         let r = ((1.0 - z * z) as M).sqrt();
@@ -67,6 +118,9 @@ impl GeometricalRay for Ray {
             position: position + direction * Self::EPS,
             direction: direction,
             frequency: self.frequency.clone(),
+            polarization: Stokes::unpolarized(),
+            reference: orthogonal(self.reference, direction),
+            inside: self.inside,
         }
     }
 
@@ -79,29 +133,90 @@ impl GeometricalRay for Ray {
             position: position + direction * Self::EPS,
             direction: direction,
             frequency: self.frequency.clone(),
+            polarization: self.polarization,
+            reference: orthogonal(self.reference, direction),
+            inside: self.inside,
         }
     }
 
     fn refract(&self, position: V3, normal: V3, factor: Factor) -> Self {
         let incident = self.direction;
-        let temp = incident.cross(normal).cross(normal);
-        let sinb = temp.length() * factor;
+        // the tangential part of the incident direction keeps its side
+        let tangent = -incident.cross(normal).cross(normal);
+        let sinb = tangent.length() * factor;
         if sinb < 1.0 {
             let cosb = (1.0 - sinb * sinb).sqrt();
-            let direction = temp * factor - normal * cosb;
+            let direction = tangent * factor - normal * cosb;
             Ray {
                 position: position + direction * Self::EPS,
                 direction: direction,
                 frequency: self.frequency.clone(),
+                polarization: self.polarization,
+                reference: orthogonal(self.reference, direction),
+                inside: !self.inside,
             }
         } else {
             self.reflect(position, normal)
         }
     }
+
+    fn redirect(&self, position: V3, direction: V3) -> Self {
+        Ray {
+            position: position + direction * Self::EPS,
+            direction: direction,
+            frequency: self.frequency.clone(),
+            polarization: self.polarization,
+            reference: orthogonal(self.reference, direction),
+            inside: self.inside,
+        }
+    }
 }
 
 impl PhotonicRay for Ray {
     fn frequency(&self) -> Frequency {
         self.frequency.clone()
     }
+
+    fn shift(&self, frequency: Frequency) -> Self {
+        Ray {
+            position: self.position,
+            direction: self.direction,
+            frequency: frequency,
+            polarization: self.polarization,
+            reference: self.reference,
+            inside: self.inside,
+        }
+    }
+}
+
+impl PolarizedRay for Ray {
+    fn align(&self, axis: V3) -> Self {
+        let reference = orthogonal(axis, self.direction);
+        let cosine = self.reference * reference;
+        let sine = self.reference.cross(reference) * self.direction.normalize();
+
+        Ray {
+            position: self.position,
+            direction: self.direction,
+            frequency: self.frequency.clone(),
+            polarization: self.polarization * &Mueller::rotation(-sine.atan2(cosine)),
+            reference: reference,
+            inside: self.inside,
+        }
+    }
+
+    fn filter(&self, mueller: &Mueller) -> (Self, Density) {
+        let (polarization, intensity) = (self.polarization * mueller).normalize();
+
+        let ray = Ray {
+            position: self.position,
+            direction: self.direction,
+            frequency: self.frequency.clone(),
+            polarization: polarization,
+            reference: self.reference,
+            inside: self.inside,
+        };
+
+        (ray, intensity / self.polarization.intensity())
+    }
 }
@@ -4,44 +4,275 @@ use super::primitive::Triangle;
 use super::primitive::IntersectInfo;
 use super::primitive::IntersectResult;
 
+use super::algebra::V3;
+
+use super::bsdf::Surface;
+
+use super::screen::Screen;
+
 use super::ray::Ray;
-use super::ray::PhotonicRay;
-use super::ray::GeometricalRay;
+
+use super::beam::Density;
+use super::beam::Frequency;
+
+use super::spectrum;
+use super::spectrum::Spectrum;
 
 use std::cmp::Ordering;
+use std::ptr;
+use std::sync::Arc;
+use std::sync::OnceLock;
 
-use super::beam::SingleFate;
+use super::integrator::Integrator;
+use super::integrator::PathTracing;
+
+use super::bidirectional::Bidirectional;
+use super::photon::PhotonMapping;
 
 use rand::Rng;
 
+/// Emitter enum refers to the primitive which emits the light at some band
+#[derive(Clone)]
+enum Emitter {
+    Sphere(usize),
+    Triangle(usize),
+}
+
+/// Heuristic enum is how the light sampling and the bsdf sampling are weighted
+/// when both of them can find the same emitter
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub enum Heuristic {
+    Balance,
+    #[default]
+    Power,
+}
+
+impl Heuristic {
+    /// weight of the strategy with the probability density `pdf`
+    /// against the other one with the density `other`
+    pub fn weight(&self, pdf: Density, other: Density) -> Density {
+        let (a, b) = (self.power(pdf), self.power(other));
+        if a > 0.0 {
+            a / (a + b)
+        } else {
+            0.0
+        }
+    }
+
+    pub(crate) fn power(&self, pdf: Density) -> Density {
+        match *self {
+            Heuristic::Balance => pdf,
+            Heuristic::Power => pdf * pdf,
+        }
+    }
+}
+
+/// Method enum is the algorithm `Scene::trace` uses along with its settings
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Method {
+    /// rays go from the eye only, the light is sampled at every vertex
+    PathTracing(PathTracing),
+    /// subpaths from the eye and from the emitters are connected
+    Bidirectional(Bidirectional),
+    /// photons from the emitters are gathered where the ray is scattered diffusely
+    PhotonMapping(PhotonMapping),
+    /// user defined algorithm, the scene containing it cannot be serialized
+    #[serde(skip_serializing, skip_deserializing)]
+    Custom(Arc<dyn Integrator>),
+}
+
+impl Default for Method {
+    fn default() -> Self {
+        Method::PathTracing(PathTracing::default())
+    }
+}
+
+impl Method {
+    pub fn custom<I>(integrator: I) -> Self
+    where
+        I: Integrator + 'static,
+    {
+        Method::Custom(Arc::new(integrator))
+    }
+}
+
+impl Integrator for Method {
+    fn pass(
+        &self,
+        scene: &Scene,
+        screen: Option<&Screen>,
+        pass: usize,
+        rng: &mut dyn Rng,
+    ) -> Option<Box<dyn Integrator>> {
+        match *self {
+            Method::PathTracing(_) => None,
+            Method::Bidirectional(ref bidirectional) => {
+                bidirectional.pass(scene, screen, pass, rng)
+            }
+            Method::PhotonMapping(ref mapping) => mapping.pass(scene, screen, pass, rng),
+            Method::Custom(ref integrator) => integrator.pass(scene, screen, pass, rng),
+        }
+    }
+
+    fn trace(&self, scene: &Scene, ray: &Ray, rng: &mut dyn Rng) -> Density {
+        match *self {
+            Method::PathTracing(ref tracing) => tracing.trace(scene, ray, rng),
+            Method::Bidirectional(ref bidirectional) => bidirectional.trace(scene, ray, rng),
+            Method::PhotonMapping(ref mapping) => mapping.trace(scene, ray, rng),
+            Method::Custom(ref integrator) => integrator.trace(scene, ray, rng),
+        }
+    }
+
+    fn splat(
+        &self,
+        scene: &Scene,
+        rng: &mut dyn Rng,
+        splat: &mut dyn FnMut(usize, &Frequency, Density),
+    ) {
+        if let Method::Custom(ref integrator) = *self {
+            integrator.splat(scene, rng, splat)
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Scene {
+    /// goes first, so the beams of the primitives are deserialized with it
+    #[serde(default = "current", deserialize_with = "spectrum::configured")]
+    spectrum: Spectrum,
     spheres: Vec<Sphere>,
     triangles: Vec<Triangle>,
+    #[serde(default)]
+    heuristic: Heuristic,
+    #[serde(default)]
+    method: Method,
+    /// collected when the scene is created or first traced after deserialization
+    #[serde(skip_serializing, skip_deserializing)]
+    emitters: OnceLock<Vec<Emitter>>,
+}
+
+// scenes saved before the spectrum was stored are made for the current one
+fn current() -> Spectrum {
+    Spectrum::current().clone()
 }
 
 impl Scene {
     pub fn new(spheres: Vec<Sphere>, triangles: Vec<Triangle>) -> Self {
-        Scene {
+        let scene = Scene {
+            spectrum: current(),
             spheres: spheres,
             triangles: triangles,
+            heuristic: Heuristic::default(),
+            method: Method::default(),
+            emitters: OnceLock::new(),
+        };
+        scene.emitters();
+        scene
+    }
+
+    pub fn with_heuristic(self, heuristic: Heuristic) -> Self {
+        Scene {
+            heuristic: heuristic,
+            ..self
+        }
+    }
+
+    pub fn with_method(self, method: Method) -> Self {
+        Scene {
+            method: method,
+            ..self
         }
     }
 
-    pub fn trace(&self, ray: &Ray, mut rng: &mut Rng) -> Vec<Ray> {
-        self.trace_internal(ray, &mut rng, 0)
+    /// bands the beams of the scene are split into
+    pub fn spectrum(&self) -> &Spectrum {
+        &self.spectrum
     }
 
-    fn intersect(&self, ray: &Ray) -> Option<IntersectResult> {
-        fn find_minimal<'a, T>(v: &'a Vec<T>, ray: &Ray) -> Option<(&'a T, IntersectInfo)>
+    /// algorithm the screen traces the rays with
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// radiance coming back along the `ray` found by the method of the scene
+    pub fn trace(&self, ray: &Ray, rng: &mut dyn Rng) -> Density {
+        self.method.trace(self, ray, rng)
+    }
+
+    /// how the strategies finding the same path are weighted
+    pub fn heuristic(&self) -> &Heuristic {
+        &self.heuristic
+    }
+
+    /// number of the primitives emitting the light at some band
+    pub fn emitter_count(&self) -> usize {
+        self.emitters().len()
+    }
+
+    pub fn emitter(&self, index: usize) -> &dyn Primitive {
+        self.primitive(&self.emitters()[index])
+    }
+
+    /// probability density per area of choosing the point of the surface and its side
+    /// when the light is sampled
+    pub fn emitter_pdf(&self, surface: &Surface) -> Density {
+        let emitters = self.emitters();
+        emitters
+            .iter()
+            .map(|emitter| self.primitive(emitter))
+            .find(|primitive| ptr::eq(primitive.surface(), surface))
+            .map(|primitive| 1.0 / (2.0 * primitive.area() * (emitters.len() as Density)))
+            .unwrap_or(0.0)
+    }
+
+    fn emitters(&self) -> &Vec<Emitter> {
+        self.emitters.get_or_init(|| {
+            let emits = |primitive: &dyn Primitive| {
+                let bsdf = primitive.surface().bsdf();
+                Frequency::all().any(|frequency| bsdf.emission(&frequency) > 0.0)
+            };
+
+            let spheres = self.spheres
+                .iter()
+                .enumerate()
+                .filter(|&(_, sphere)| emits(sphere))
+                .map(|(i, _)| Emitter::Sphere(i));
+            let triangles = self.triangles
+                .iter()
+                .enumerate()
+                .filter(|&(_, triangle)| emits(triangle))
+                .map(|(i, _)| Emitter::Triangle(i));
+
+            spheres.chain(triangles).collect()
+        })
+    }
+
+    fn primitive(&self, emitter: &Emitter) -> &dyn Primitive {
+        match *emitter {
+            Emitter::Sphere(i) => &self.spheres[i],
+            Emitter::Triangle(i) => &self.triangles[i],
+        }
+    }
+
+    /// probability density of finding the direction toward the surface by the light sampling
+    pub fn light_pdf(&self, surface: &Surface, origin: V3, direction: V3) -> Density {
+        let emitters = self.emitters();
+        emitters
+            .iter()
+            .map(|emitter| self.primitive(emitter))
+            .find(|primitive| ptr::eq(primitive.surface(), surface))
+            .map(|primitive| primitive.pdf(origin, direction) / (emitters.len() as Density))
+            .unwrap_or(0.0)
+    }
+
+    /// the closest hit of the ray
+    pub fn intersect(&self, ray: &Ray) -> Option<IntersectResult<'_>> {
+        fn find_minimal<'a, T>(v: &'a [T], ray: &Ray) -> Option<(&'a T, IntersectInfo)>
             where
                 T: Primitive,
         {
             v.iter()
-                .flat_map(|primitive| match primitive.intersect(ray) {
-                    Some(info) => Some((primitive, info)),
-                    None => None,
-                })
+                .flat_map(|primitive| primitive.intersect(ray).map(|info| (primitive, info)))
                 .min_by(|lhs, rhs| {
                     lhs.1.partial_cmp(&rhs.1).unwrap_or(Ordering::Less)
                 })
@@ -64,36 +295,27 @@ impl Scene {
         }
     }
 
-    fn trace_internal(&self, ray: &Ray, mut rng: &mut Rng, level: usize) -> Vec<Ray> {
-        let maximal_level = 7;
+}
 
-        if level < maximal_level {
-            if let Some(result) = self.intersect(ray) {
-                let fate = result.material.fate(&ray.frequency(), &mut rng);
+#[cfg(test)]
+mod test {
+    use super::*;
 
-                use self::SingleFate::*;
-                let new_ray = match fate.single {
-                    Decay => None,
-                    Diffuse => Some(ray.diffuse(result.position, result.normal, &mut rng)),
-                    Reflect => Some(ray.reflect(result.position, result.normal)),
-                    Refract(factor) => Some(ray.refract(result.position, result.normal, factor)),
-                };
+    use super::super::fixture;
 
-                let mut rays = Vec::with_capacity(maximal_level + 1);
-                if fate.emission {
-                    rays.push((*ray).clone());
-                }
+    use rand;
 
-                if let Some(new_ray) = new_ray {
-                    rays.append(&mut self.trace_internal(&new_ray, rng, level + 1));
-                }
+    #[test]
+    fn next_event() {
+        // white lambertian floor under the spherical lamp, the light is seen in the cone
+        let mut rng = rand::thread_rng();
+        for heuristic in [Heuristic::Balance, Heuristic::Power].iter() {
+            let scene = fixture::lamp(Method::default()).with_heuristic(heuristic.clone());
 
-                rays
-            } else {
-                Vec::with_capacity(maximal_level + 1)
-            }
-        } else {
-            Vec::with_capacity(maximal_level + 1)
+            let samples = 20000;
+            let ray = fixture::ray();
+            let sum = (0..samples).fold(0.0, |sum, _| sum + scene.trace(&ray, &mut rng));
+            assert!((sum / (samples as Density) - fixture::expected()).abs() < 0.01);
         }
     }
 }
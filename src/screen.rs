@@ -2,14 +2,32 @@ use super::algebra::V3;
 use super::algebra::M;
 
 use super::ray::Ray;
-use super::ray::PhotonicRay;
+use super::ray::PolarizedRay;
 use super::scene::Scene;
+use super::integrator::Integrator;
 
 use super::beam::Frequency;
 use super::beam::Beam;
-use super::beam::RGB;
 use super::beam::Density;
 
+use super::polarization::Mueller;
+
+use super::sensor::Sensor;
+use super::sensor::SensorKind;
+
+use super::spectrum::Spectrum;
+
+use super::metropolis::Metropolis;
+
+use super::photometry::Calibration;
+
+use super::colorspace::Observer;
+use super::colorspace::ColorSpace;
+use super::colorspace::XYZ;
+use super::colorspace::WhiteBalance;
+
+use std::error::Error;
+use std::fmt;
 use std::ops::AddAssign;
 
 use rand::Rng;
@@ -32,12 +50,32 @@ pub struct Eye {
     pub width: M,
     pub height: M,
     pub distance: M,
+
+    /// angle of the linear polarizer axis measured from `right` toward `up`
+    #[serde(default)]
+    pub polarizer: Option<M>,
 }
 
-#[derive(Serialize, Deserialize)]
+impl Eye {
+    // the ray through the polarizer along with its transmittance
+    fn ray(&self, direction: V3, frequency: Frequency) -> (Ray, Density) {
+        let ray = Ray::new(self.position, direction, frequency);
+        match self.polarizer {
+            Some(angle) => {
+                let axis = self.right * angle.cos() + self.up * angle.sin();
+                ray.align(axis).filter(&Mueller::linear_polarizer(0.0))
+            }
+            None => (ray, 1.0),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Screen {
     format: Size,
     eye: Eye,
+    #[serde(default)]
+    sensor: Sensor,
 }
 
 impl Screen {
@@ -45,92 +83,391 @@ impl Screen {
         Screen {
             format: format,
             eye: eye,
+            sensor: Sensor::default(),
+        }
+    }
+
+    /// color matching functions the spectra are projected on
+    pub fn with_observer(self, observer: Observer) -> Self {
+        self.with_sensor(Sensor::observer(&observer))
+    }
+
+    /// accumulate the whole spectrum of every pixel, see `Image::project`
+    pub fn multispectral(self) -> Self {
+        self.with_sensor(Sensor::spectral())
+    }
+
+    /// spectral response of the camera used instead of the observer
+    pub fn with_sensor(self, sensor: Sensor) -> Self {
+        Screen {
+            sensor: sensor,
+            ..self
         }
     }
 
     pub fn create_image(&self) -> Image {
-        Image::new(self.format.clone())
+        let kind = self.sensor.kind().clone();
+        let spectrum = if kind == SensorKind::Spectral {
+            Some(Spectrum::current().clone())
+        } else {
+            None
+        };
+        Image::new(self.format.clone(), self.sensor.size(), kind, spectrum)
     }
 
-    pub fn sample(&self, scene: &Scene, image: &mut Image, mut rng: &mut Rng) {
+    pub fn sample(&self, scene: &Scene, image: &mut Image, mut rng: &mut dyn Rng) {
         let format = &self.format;
-        let eye = &self.eye;
-
-        let red = Beam::red();
-        let green = Beam::green();
-        let blue = Beam::blue();
+        let method = scene.method();
+        let pass = method.pass(scene, Some(self), image.count, &mut rng);
+        let integrator = pass.as_ref().map(|pass| pass.as_ref()).unwrap_or(method);
 
         for i in 0..format.vertical_count {
             for j in 0..format.horizontal_count {
+                let beam = Beam::from_bands(|frequency| {
+                    let (ray, weight) = self.ray(i, j, frequency.clone(), &mut rng);
+                    integrator.trace(scene, &ray, &mut rng) * weight
+                });
+
+                let pixel = &mut image.data[i * format.horizontal_count + j];
+                for (value, projection) in pixel.iter_mut().zip(self.sensor.project(&beam)) {
+                    *value += projection;
+                }
+            }
+        }
+
+        let data = &mut image.data;
+        integrator.splat(scene, &mut rng, &mut |pixel, frequency, radiance| {
+            for (value, response) in data[pixel].iter_mut().zip(self.sensor.response(frequency)) {
+                *value += response * radiance;
+            }
+        });
 
-                let direction_calc = |dx: M, dy: M| {
-                    let x = eye.width * (((j as M) + dx) / (format.horizontal_count as M) - 0.5);
-                    let y = eye.height * (((i as M) + dy) / (format.vertical_count as M) - 0.5);
-                    let direction = eye.forward * eye.distance + eye.right * x + eye.up * y;
-                    direction.normalize()
-                };
-
-                let beam = (0..Beam::SIZE).into_iter().fold(
-                    Beam::default(),
-                    |beam, k| {
-                        let dx = Range::new(-0.5, 0.5).sample(&mut rng);
-                        let dy = Range::new(-0.5, 0.5).sample(&mut rng);
-
-                        scene
-                            .trace(
-                                &Ray::new(eye.position, direction_calc(dx, dy), Frequency::new(k)),
-                                &mut rng,
-                            )
-                            .into_iter()
-                            .fold(beam, |beam, ray| beam + ray.frequency())
-                    },
-                );
-
-                let rgb = &mut image.data[i * format.horizontal_count + j];
-                *rgb = rgb.clone() +
-                    RGB::new(
-                        beam.clone() * &red,
-                        beam.clone() * &green,
-                        beam.clone() * &blue,
-                    )
+        image.count += 1;
+    }
+
+    /// the pass of the Metropolis light transport, the chain is mutated as many times
+    /// as `sample` traces rays, so the passes of both can be added to the same image
+    pub fn sample_metropolis(
+        &self,
+        scene: &Scene,
+        image: &mut Image,
+        metropolis: &Metropolis,
+        mut rng: &mut dyn Rng,
+    ) {
+        let format = &self.format;
+        let pixels = format.horizontal_count * format.vertical_count;
+        let bands = Spectrum::current().size();
+        let method = scene.method();
+        let pass = method.pass(scene, Some(self), image.count, &mut rng);
+        let integrator = pass.as_ref().map(|pass| pass.as_ref()).unwrap_or(method);
+
+        let mut splats = vec![vec![0.0; bands]; pixels];
+        metropolis.run(
+            pixels * bands,
+            &mut rng,
+            |mut rng| {
+                let i = index(format.vertical_count, &mut rng);
+                let j = index(format.horizontal_count, &mut rng);
+                let frequency = Frequency::new(index(bands, &mut rng));
+                let (ray, weight) = self.ray(i, j, frequency.clone(), &mut rng);
+                let radiance = integrator.trace(scene, &ray, &mut rng) * weight;
+                (i * format.horizontal_count + j, frequency, radiance)
+            },
+            |pixel, frequency, weight| splats[pixel][frequency.index()] += weight,
+        );
+        integrator.splat(scene, &mut rng, &mut |pixel, frequency, radiance| {
+            splats[pixel][frequency.index()] += radiance
+        });
+
+        for (pixel, splat) in image.data.iter_mut().zip(splats) {
+            let beam = Beam::from_bands(|frequency| splat[frequency.index()]);
+            for (value, projection) in pixel.iter_mut().zip(self.sensor.project(&beam)) {
+                *value += projection;
             }
         }
 
-        image.count = image.count + 1;
+        image.count += 1;
+    }
+
+    pub fn eye(&self) -> &Eye {
+        &self.eye
+    }
+
+    pub fn size(&self) -> Size {
+        self.format.clone()
+    }
+
+    /// pixel seen from the eye in the `direction`, none outside the screen
+    pub fn pixel(&self, direction: V3) -> Option<usize> {
+        // the centre of the pixel is at its corner counted from the corner of the screen
+        let (format, eye) = (&self.format, &self.eye);
+        let (width, height) = (format.horizontal_count as M, format.vertical_count as M);
+        let (x, y) = self.project(direction)?;
+        let j = ((x / eye.width + 0.5) * width + 0.5).floor();
+        let i = ((y / eye.height + 0.5) * height + 0.5).floor();
+        if j >= 0.0 && j < width && i >= 0.0 && i < height {
+            Some((i as usize) * format.horizontal_count + (j as usize))
+        } else {
+            None
+        }
+    }
+
+    /// probability density per solid angle of the `direction` of the ray traced
+    /// through the random point of the whole screen
+    pub fn pdf(&self, direction: V3) -> Density {
+        let eye = &self.eye;
+        let (normal, direction) = (eye.right.cross(eye.up), direction.normalize());
+        let cosine = (normal * direction).abs();
+        let distance = (normal * eye.forward) * eye.distance / (normal * direction);
+        if distance > 0.0 && cosine > 0.0 {
+            distance * distance / (eye.width * eye.height * cosine)
+        } else {
+            0.0
+        }
+    }
+
+    // point where the ray in the `direction` crosses the screen, the coordinates are
+    // along `right` and `up` from the centre, none behind the eye
+    fn project(&self, direction: V3) -> Option<(M, M)> {
+        let eye = &self.eye;
+        let (right, up) = (eye.right, eye.up);
+        let normal = right.cross(up);
+        let distance = (normal * eye.forward) * eye.distance / (normal * direction);
+        // the direction parallel to the screen gives the infinite or undefined distance
+        if !distance.is_finite() || distance <= 0.0 {
+            return None;
+        }
+
+        let offset = direction * distance - eye.forward * eye.distance;
+        let (rr, ru, uu) = (right * right, right * up, up * up);
+        let (a, b) = (right * offset, up * offset);
+        let determinant = rr * uu - ru * ru;
+        Some(((a * uu - b * ru) / determinant, (b * rr - a * ru) / determinant))
+    }
+
+    // ray through the random point of the pixel in the row `i` and the column `j`
+    // along with the transmittance of the polarizer
+    fn ray(
+        &self,
+        i: usize,
+        j: usize,
+        frequency: Frequency,
+        mut rng: &mut dyn Rng,
+    ) -> (Ray, Density) {
+        let (format, eye) = (&self.format, &self.eye);
+        let dx = Range::new(-0.5, 0.5).sample(&mut rng);
+        let dy = Range::new(-0.5, 0.5).sample(&mut rng);
+
+        let x = eye.width * (((j as M) + dx) / (format.horizontal_count as M) - 0.5);
+        let y = eye.height * (((i as M) + dy) / (format.vertical_count as M) - 0.5);
+        let direction = eye.forward * eye.distance + eye.right * x + eye.up * y;
+        eye.ray(direction.normalize(), frequency)
     }
 }
 
+// random index below the `count`, the close random numbers give the close indices
+fn index(count: usize, mut rng: &mut dyn Rng) -> usize {
+    let value: M = Range::new(0.0, 1.0).sample(&mut rng);
+    ((value * (count as M)) as usize).min(count - 1)
+}
+
+/// Image struct accumulates the values of the sensor channels for every pixel
 #[derive(Serialize, Deserialize)]
 pub struct Image {
     format: Size,
-    data: Vec<RGB>,
+    data: Vec<Vec<Density>>,
     count: usize,
+    /// what the channels mean
+    #[serde(default)]
+    sensor: SensorKind,
+    /// bands of the multispectral image
+    #[serde(default)]
+    spectrum: Option<Spectrum>,
+}
+
+/// ImageError enum is why the image cannot be converted
+#[derive(Debug)]
+pub enum ImageError {
+    /// the channels are neither X, Y and Z nor the bands of the spectrum
+    Channels(SensorKind),
+    /// only the multispectral image can be projected on the other sensor
+    NotMultispectral,
+    /// the bands of the image differ from the current spectrum
+    Spectrum,
+    /// `Y` of the observer is not the photopic luminance
+    Luminance(Observer),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ImageError::Channels(ref kind) => {
+                write!(f, "the image taken by the {:?} sensor has no colors", kind)
+            }
+            ImageError::NotMultispectral => write!(f, "the image is not multispectral"),
+            ImageError::Spectrum => write!(f, "the image is taken with the different spectrum"),
+            ImageError::Luminance(ref observer) => {
+                write!(f, "the image taken by the {:?} observer has no luminance", observer)
+            }
+        }
+    }
 }
 
+impl Error for ImageError {}
+
 impl Image {
-    fn new(format: Size) -> Self {
+    fn new(format: Size, channels: usize, sensor: SensorKind, spectrum: Option<Spectrum>) -> Self {
         let data = (0..(format.vertical_count * format.horizontal_count))
-            .into_iter()
-            .map(|_| RGB::default())
+            .map(|_| vec![0.0; channels])
             .collect();
 
         Image {
             format: format,
             data: data,
             count: 0,
+            sensor: sensor,
+            spectrum: spectrum,
         }
     }
 
-    pub fn bitmap(self, scale: Density) -> Vec<u8> {
+    /// the same image with every pixel adapted to the white of the output,
+    /// the image should be taken by the observer
+    pub fn balance(self, balance: &WhiteBalance) -> Result<Self, ImageError> {
+        self.observer()?;
+        let data = self.data
+            .iter()
+            .map(|pixel| {
+                let xyz = balance.apply(&Self::xyz(pixel));
+                vec![xyz.x(), xyz.y(), xyz.z()]
+            })
+            .collect();
+        Ok(Image { data: data, ..self })
+    }
+
+    /// pixels converted to the color space, `scale` is the exposure,
+    /// the image should be taken by the observer
+    pub fn bitmap(self, scale: Density, space: ColorSpace) -> Result<Vec<u8>, ImageError> {
+        self.observer()?;
         let format = &self.format;
         let capacity = format.horizontal_count * format.vertical_count * 3;
 
         let mut result = Vec::with_capacity(capacity);
-        for &ref pixel in self.data.iter() {
-            (pixel.clone() * scale / self.count).update_raw(&mut result);
+        for pixel in self.data.iter() {
+            space.encode(&(Self::xyz(pixel) * scale / self.count)).update_raw(&mut result);
+        }
+
+        Ok(result)
+    }
+
+    /// the multispectral image as the sensor would take it
+    pub fn project(&self, sensor: &Sensor) -> Result<Image, ImageError> {
+        match self.spectrum {
+            Some(ref spectrum) if spectrum == Spectrum::current() => (),
+            Some(_) => return Err(ImageError::Spectrum),
+            None => return Err(ImageError::NotMultispectral),
+        }
+
+        let data = self.data
+            .iter()
+            .map(|pixel| {
+                let beam = Beam::from_bands(|frequency| pixel[frequency.index()]);
+                sensor.project(&beam)
+            })
+            .collect();
+
+        Ok(Image {
+            format: self.format.clone(),
+            data: data,
+            count: self.count,
+            sensor: sensor.kind().clone(),
+            spectrum: None,
+        })
+    }
+
+    /// mean values of the channels in absolute units, the multispectral image
+    /// has the spectral radiance in W/(sr m² nm) of every band
+    pub fn radiance(&self, calibration: &Calibration) -> Vec<Vec<Density>> {
+        self.channels()
+            .into_iter()
+            .map(|pixel| pixel.into_iter().map(|value| value * calibration.radiance()).collect())
+            .collect()
+    }
+
+    /// luminance in cd/m² of every pixel, the image should be multispectral
+    /// or taken by the 1931 observer, whose `Y` is the luminance
+    pub fn luminance(&self, calibration: &Calibration) -> Result<Vec<Density>, ImageError> {
+        let spectral = self.sensor == SensorKind::Spectral;
+        match self.sensor {
+            SensorKind::Spectral | SensorKind::Observer(Observer::Cie1931) => (),
+            SensorKind::Observer(ref observer) => {
+                return Err(ImageError::Luminance(observer.clone()))
+            }
+            ref kind => return Err(ImageError::Channels(kind.clone())),
+        }
+
+        let flat = calibration.luminance(&Beam::flat(1.0));
+        Ok(self.channels()
+            .into_iter()
+            .map(|pixel| if spectral {
+                calibration.luminance(&Beam::from_bands(|f| pixel[f.index()]))
+            } else {
+                Self::xyz(&pixel).y() * flat
+            })
+            .collect())
+    }
+
+    /// header and data of the ENVI file with the mean values of the channels,
+    /// the data are little endian 32-bit floats in band sequential order, top row first
+    pub fn envi(&self) -> (String, Vec<u8>) {
+        let format = &self.format;
+        let channels = self.data.first().map(|pixel| pixel.len()).unwrap_or(0);
+
+        let mut header = String::from("ENVI\n");
+        header += &format!("samples = {}\n", format.horizontal_count);
+        header += &format!("lines = {}\n", format.vertical_count);
+        header += &format!("bands = {}\n", channels);
+        header += "header offset = 0\nfile type = ENVI Standard\ndata type = 4\n";
+        header += "interleave = bsq\nbyte order = 0\n";
+        if let Some(ref spectrum) = self.spectrum {
+            let wavelengths = (0..spectrum.size())
+                .map(|i| format!("{:.3}", spectrum.wavelength(i)))
+                .collect::<Vec<_>>();
+            header += "wavelength units = Nanometers\n";
+            header += &format!("wavelength = {{{}}}\n", wavelengths.join(", "));
+        }
+
+        let pixels = self.channels();
+        let width = format.horizontal_count.max(1);
+        let rows = pixels.chunks(width).rev().flatten().collect::<Vec<_>>();
+        let mut data = Vec::with_capacity(rows.len() * channels * 4);
+        for k in 0..channels {
+            for pixel in rows.iter() {
+                data.extend_from_slice(&(pixel[k] as f32).to_le_bytes());
+            }
         }
 
-        result
+        (header, data)
+    }
+
+    /// mean values of the sensor channels row by row
+    pub fn channels(&self) -> Vec<Vec<Density>> {
+        let count = self.count.max(1) as Density;
+        self.data
+            .iter()
+            .map(|pixel| pixel.iter().map(|value| value / count).collect())
+            .collect()
+    }
+
+    // the channels are X, Y and Z
+    fn observer(&self) -> Result<(), ImageError> {
+        match self.sensor {
+            SensorKind::Observer(_) => Ok(()),
+            ref kind => Err(ImageError::Channels(kind.clone())),
+        }
+    }
+
+    fn xyz(pixel: &[Density]) -> XYZ {
+        XYZ::new(pixel[0], pixel[1], pixel[2])
     }
 
     pub fn size(&self) -> Size {
@@ -141,10 +478,84 @@ impl Image {
 impl AddAssign<Image> for Image {
     fn add_assign(&mut self, rhs: Image) {
         assert!(self.format == rhs.format);
+        assert!(
+            self.sensor == rhs.sensor && self.spectrum == rhs.spectrum,
+            "the images are taken by the different sensors"
+        );
+        let channels = |image: &Image| image.data.first().map(|pixel| pixel.len());
+        assert!(channels(self) == channels(&rhs), "the images have different channels");
 
         self.count += rhs.count;
-        for i in 0..self.data.len() {
-            self.data[i] = self.data[i].clone() + rhs.data[i].clone();
+        for (pixel, rhs) in self.data.iter_mut().zip(rhs.data) {
+            for (value, rhs) in pixel.iter_mut().zip(rhs) {
+                *value += rhs;
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn multispectral() {
+        let format = Size {
+            horizontal_count: 2,
+            vertical_count: 1,
+        };
+        let size = Spectrum::current().size();
+        let spectrum = Some(Spectrum::current().clone());
+        let mut image = Image::new(format, size, SensorKind::Spectral, spectrum);
+        image.data[1][size / 2] = 2.0;
+        image.count = 1;
+
+        let observer = Sensor::default();
+        let projected = image.project(&observer).unwrap();
+        let beam = Beam::default() + Frequency::new(size / 2);
+        let expected = observer.project(&(beam * 2.0));
+        assert_eq!(projected.channels()[1], expected);
+        assert_eq!(projected.channels()[0], vec![0.0; 3]);
+
+        let calibration = Calibration::new(10.0);
+        let luminance = calibration.luminance(&(Beam::default() + Frequency::new(size / 2)));
+        assert!((image.luminance(&calibration).unwrap()[1] - 2.0 * luminance).abs() < 1.0e-9);
+        assert!((projected.luminance(&calibration).unwrap()[1] - 2.0 * luminance).abs() < 1.0e-9);
+
+        let (header, data) = image.envi();
+        assert!(header.contains(&format!("bands = {}", size)));
+        assert_eq!(data.len(), 2 * size * 4);
+    }
+
+    #[test]
+    fn camera() {
+        // three channels of the camera are not the colors of the observer
+        let format = Size {
+            horizontal_count: 1,
+            vertical_count: 1,
+        };
+        let camera = Image::new(format.clone(), 3, SensorKind::Camera, None);
+        assert!(camera.luminance(&Calibration::new(10.0)).is_err());
+        assert!(camera.project(&Sensor::default()).is_err());
+        assert!(camera.bitmap(1.0, ColorSpace::Srgb).is_err());
+
+        let observer = Image::new(format.clone(), 3, SensorKind::Observer(Observer::Cie1931), None);
+        assert_eq!(observer.bitmap(1.0, ColorSpace::Srgb).ok().map(|data| data.len()), Some(3));
+    }
+
+    #[test]
+    fn wide_observer() {
+        // `Y` of the 10-degree observer gives the colors but not the luminance
+        let format = Size {
+            horizontal_count: 1,
+            vertical_count: 1,
+        };
+        let calibration = Calibration::new(10.0);
+        let wide = Image::new(format.clone(), 3, SensorKind::Observer(Observer::Cie1964), None);
+        assert!(wide.luminance(&calibration).is_err());
+        assert!(wide.bitmap(1.0, ColorSpace::Srgb).is_ok());
+
+        let standard = Image::new(format, 3, SensorKind::Observer(Observer::Cie1931), None);
+        assert!(standard.luminance(&calibration).is_ok());
+    }
+}
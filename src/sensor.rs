@@ -0,0 +1,91 @@
+use super::beam::Beam;
+use super::beam::Frequency;
+use super::beam::Density;
+
+use super::colorspace::Observer;
+
+/// SensorKind enum is what the channels of the sensor mean
+#[derive(Default, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum SensorKind {
+    /// arbitrary sensitivities, nothing is known about the channels
+    #[default]
+    Camera,
+    /// color matching functions of the observer, the channels are X, Y and Z
+    Observer(Observer),
+    /// one channel for every band
+    Spectral,
+}
+
+/// Sensor struct is a spectral sensitivity of every channel of the detector,
+/// the image stores the projections of the spectra on the channels
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Sensor {
+    channels: Vec<Beam>,
+    #[serde(default)]
+    kind: SensorKind,
+}
+
+impl Sensor {
+    /// channels given by their sensitivities, like the curves of the camera
+    pub fn new(channels: Vec<Beam>) -> Self {
+        Sensor {
+            channels: channels,
+            kind: SensorKind::Camera,
+        }
+    }
+
+    /// one channel for every band, the image keeps the whole spectrum
+    pub fn spectral() -> Self {
+        Sensor {
+            channels: Frequency::all().map(|frequency| Beam::default() + frequency).collect(),
+            kind: SensorKind::Spectral,
+        }
+    }
+
+    /// human eye, the channels are X, Y and Z
+    pub fn observer(observer: &Observer) -> Self {
+        let (x, y, z) = observer.matching();
+        Sensor {
+            channels: vec![x, y, z],
+            kind: SensorKind::Observer(observer.clone()),
+        }
+    }
+
+    pub fn kind(&self) -> &SensorKind {
+        &self.kind
+    }
+
+    pub fn size(&self) -> usize {
+        self.channels.len()
+    }
+
+    pub fn project(&self, beam: &Beam) -> Vec<Density> {
+        self.channels.iter().map(|channel| beam.clone() * channel).collect()
+    }
+
+    /// projection of the unit density at the band
+    pub fn response(&self, frequency: &Frequency) -> Vec<Density> {
+        self.channels.iter().map(|channel| channel.density(frequency)).collect()
+    }
+}
+
+impl Default for Sensor {
+    fn default() -> Self {
+        Self::observer(&Observer::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use super::super::beam::Frequency;
+
+    #[test]
+    fn channels() {
+        let band = Beam::default() + Frequency::new(3);
+        let sensor = Sensor::new(vec![Beam::flat(1.0), band.clone(), Beam::default()]);
+        assert_eq!(sensor.size(), 3);
+        assert_eq!(sensor.project(&band), vec![1.0, 1.0, 0.0]);
+    }
+}
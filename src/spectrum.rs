@@ -0,0 +1,252 @@
+use super::algebra::M;
+
+use std::error::Error;
+use std::fmt;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::de::Error as DeError;
+
+static CURRENT: OnceLock<Spectrum> = OnceLock::new();
+
+/// Spectrum struct describes the bands the light is split into,
+/// the bands are uniform in frequency like the color matching table
+#[derive(Clone, PartialEq, Serialize)]
+pub struct Spectrum {
+    size: usize,
+    shortest: M,
+    longest: M,
+}
+
+/// SpectrumError enum is why the spectrum is not valid
+#[derive(Debug)]
+pub enum SpectrumError {
+    /// there is no band
+    Empty,
+    /// the wavelengths are not positive, or the centres of several bands coincide
+    Range { shortest: M, longest: M },
+}
+
+impl fmt::Display for SpectrumError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SpectrumError::Empty => write!(f, "spectrum must have at least one band"),
+            SpectrumError::Range { shortest: shortest, longest: longest } => write!(
+                f,
+                "wrong wavelength range from {} to {} nanometres",
+                shortest, longest
+            ),
+        }
+    }
+}
+
+impl Error for SpectrumError {}
+
+impl<'de> Deserialize<'de> for Spectrum {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Raw {
+            size: usize,
+            shortest: M,
+            longest: M,
+        }
+
+        let raw = Raw::deserialize(deserializer)?;
+        Spectrum::new(raw.size, raw.shortest, raw.longest).map_err(D::Error::custom)
+    }
+}
+
+impl Spectrum {
+    /// `size` bands with centres from `shortest` to `longest` wavelength in nanometres,
+    /// the single band covers the whole range
+    pub fn new(size: usize, shortest: M, longest: M) -> Result<Self, SpectrumError> {
+        let ordered = if size > 1 { shortest < longest } else { shortest <= longest };
+        if size < 1 {
+            Err(SpectrumError::Empty)
+        } else if !(0.0 < shortest && ordered) {
+            Err(SpectrumError::Range {
+                shortest: shortest,
+                longest: longest,
+            })
+        } else {
+            Ok(Spectrum {
+                size: size,
+                shortest: shortest,
+                longest: longest,
+            })
+        }
+    }
+
+    /// 24 bands from 392 to 714 nanometres, every second entry of the color matching table
+    pub fn visible() -> Self {
+        Spectrum {
+            size: 24,
+            shortest: 1.0e7 / 25500.0,
+            longest: 1.0e7 / 14000.0,
+        }
+    }
+
+    /// set the spectrum used by the whole process, it should be done before any beam
+    /// is created, returns the spectrum back if it is too late
+    pub fn configure(spectrum: Spectrum) -> Result<(), Spectrum> {
+        CURRENT.set(spectrum)
+    }
+
+    pub fn current() -> &'static Spectrum {
+        CURRENT.get_or_init(Self::visible)
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// wavelength of the centre of the band in nanometres
+    pub fn wavelength(&self, index: usize) -> M {
+        1.0 / self.frequency(index)
+    }
+
+    /// shortest and longest wavelength of the band in nanometres
+    pub fn bounds(&self, index: usize) -> (M, M) {
+        let half = self.step() / 2.0;
+        let frequency = self.frequency(index);
+        if self.size > 1 {
+            (1.0 / (frequency + half), 1.0 / (frequency - half))
+        } else {
+            (self.shortest, self.longest)
+        }
+    }
+
+    /// index of the band containing the wavelength in nanometres
+    pub fn index(&self, wavelength: M) -> Option<usize> {
+        let (shortest, _) = self.bounds(0);
+        let (_, longest) = self.bounds(self.size - 1);
+        if wavelength < shortest || wavelength > longest {
+            None
+        } else if self.size > 1 {
+            let highest = 1.0 / self.shortest;
+            let index = ((highest - 1.0 / wavelength) / self.step()).round() as usize;
+            Some(index.min(self.size - 1))
+        } else {
+            Some(0)
+        }
+    }
+
+    // distance between centres of neighboring bands in inverse nanometres
+    fn step(&self) -> M {
+        if self.size > 1 {
+            (1.0 / self.shortest - 1.0 / self.longest) / ((self.size - 1) as M)
+        } else {
+            1.0 / self.shortest - 1.0 / self.longest
+        }
+    }
+
+    // frequency of the centre of the band in inverse nanometres
+    fn frequency(&self, index: usize) -> M {
+        let (highest, lowest) = (1.0 / self.shortest, 1.0 / self.longest);
+        if self.size > 1 {
+            highest - self.step() * (index as M)
+        } else {
+            (highest + lowest) / 2.0
+        }
+    }
+}
+
+/// deserialize the spectrum the object is made for and use it for the whole process,
+/// fails if the process already uses the different one
+pub(crate) fn configured<'de, D>(deserializer: D) -> Result<Spectrum, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let spectrum = Spectrum::deserialize(deserializer)?;
+    match Spectrum::configure(spectrum) {
+        Ok(()) => Ok(Spectrum::current().clone()),
+        Err(ref spectrum) if spectrum == Spectrum::current() => Ok(spectrum.clone()),
+        Err(_) => Err(D::Error::custom("the spectrum differs from the one already in use")),
+    }
+}
+
+/// deserialize the values given for every band of the current spectrum
+pub(crate) fn bands<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    let values = Vec::<T>::deserialize(deserializer)?;
+    let size = Spectrum::current().size();
+    if values.len() == size {
+        Ok(values)
+    } else {
+        Err(D::Error::invalid_length(values.len(), &"one value for every band of the spectrum"))
+    }
+}
+
+impl Default for Spectrum {
+    fn default() -> Self {
+        Self::visible()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use super::super::color;
+
+    use serde::de::value;
+    use serde::de::value::SeqDeserializer;
+
+    #[test]
+    fn visible() {
+        // bands of the visible spectrum coincide with every second entry of the table
+        let spectrum = Spectrum::visible();
+        let table = color::table();
+        for i in 0..spectrum.size() {
+            assert!((spectrum.wavelength(i) - table[i * 2].0 as M).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn bounds() {
+        let spectrum = Spectrum::new(7, 400.0, 700.0).unwrap();
+        for i in 0..spectrum.size() {
+            let (shortest, longest) = spectrum.bounds(i);
+            let wavelength = spectrum.wavelength(i);
+            assert!(shortest < wavelength && wavelength < longest);
+            assert_eq!(spectrum.index(wavelength), Some(i));
+            assert_eq!(spectrum.index(shortest + 0.01), Some(i));
+            assert_eq!(spectrum.index(longest - 0.01), Some(i));
+            if i > 0 {
+                assert!((spectrum.bounds(i - 1).1 - shortest).abs() < 1.0e-9);
+            }
+        }
+        assert_eq!(spectrum.index(300.0), None);
+        assert_eq!(spectrum.index(800.0), None);
+    }
+
+    #[test]
+    fn range() {
+        // several bands cannot share the same centre, the single one can be narrow
+        assert!(Spectrum::new(0, 400.0, 700.0).is_err());
+        assert!(Spectrum::new(3, 500.0, 500.0).is_err());
+        assert!(Spectrum::new(3, 700.0, 400.0).is_err());
+        assert!(Spectrum::new(3, 0.0, 400.0).is_err());
+        assert!(Spectrum::new(1, 500.0, 500.0).is_ok());
+    }
+
+    #[test]
+    fn deserialize() {
+        // beams of the different spectrum are rejected instead of padded
+        let deserialize = |size: usize| {
+            let deserializer = SeqDeserializer::<_, value::Error>::new(vec![1.0; size].into_iter());
+            bands::<_, M>(deserializer).map(|values| values.len())
+        };
+        let size = Spectrum::current().size();
+        assert_eq!(deserialize(size).ok(), Some(size));
+        assert!(deserialize(size + 1).is_err());
+        assert!(deserialize(size - 1).is_err());
+    }
+}
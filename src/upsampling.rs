@@ -0,0 +1,197 @@
+use super::algebra::V3;
+use super::algebra::M;
+
+use super::beam::Beam;
+use super::beam::Density;
+use super::beam::Frequency;
+
+use super::color;
+use super::colorspace::ColorSpace;
+use super::colorspace::Observer;
+use super::colorspace::XYZ;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+/// Reflectance spectra for colors given by artists
+impl Beam {
+    /// smooth reflectance for the gamma encoded sRGB color, components are from 0 to 1,
+    /// the spectrum is a sigmoid of a quadratic polynomial (Jakob and Hanika, 2019)
+    /// fitted to have the color under D65 seen by the 1931 observer, so it never leaves
+    /// the range from 0 to 1, the fits are cached
+    pub fn from_srgb(r: Density, g: Density, b: Density) -> Self {
+        let decode = |c: Density| {
+            let c = c.clamp(0.0, 1.0);
+            let linear = if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            };
+            // sigmoid reaches neither 0 nor 1
+            linear.clamp(1.0e-4, 1.0 - 1.0e-4)
+        };
+
+        let coefficients = fit(V3::new(decode(r), decode(g), decode(b)));
+        Self::from_function(|wavelength| sigmoid(polynomial(coefficients, wavelength)))
+    }
+}
+
+fn sigmoid(z: M) -> M {
+    0.5 + z / (2.0 * (1.0 + z * z).sqrt())
+}
+
+// the wavelength is mapped onto the range from 0 to 1 to keep coefficients reasonable
+fn position(wavelength: M) -> M {
+    let last = color::CIE_START + color::CIE_STEP * ((color::CIE_SIZE - 1) as M);
+    (wavelength - color::CIE_START) / (last - color::CIE_START)
+}
+
+fn polynomial(coefficients: V3, wavelength: M) -> M {
+    let x = position(wavelength);
+    coefficients * V3::new(x * x, x, 1.0)
+}
+
+// linear sRGB of unit reflectance at every sample `Beam::from_function` takes under D65,
+// the sum over the samples is the color of the reflectance in the image
+fn nodes() -> &'static [(M, V3)] {
+    static NODES: OnceLock<Vec<(M, V3)>> = OnceLock::new();
+    NODES.get_or_init(|| {
+        let (x, y, z) = Observer::Cie1931.matching();
+        let d65 = Beam::d65();
+        let white = y.clone() * &d65;
+        Frequency::all()
+            .flat_map(|frequency| {
+                let samples = frequency.samples();
+                let scale = d65.density(&frequency) / white / (samples.len() as M);
+                let density = |beam: &Beam| beam.density(&frequency) * scale;
+                let xyz = XYZ::new(density(&x), density(&y), density(&z));
+                let (r, g, b) = ColorSpace::Srgb.linear(&xyz).components();
+                samples.map(move |wavelength| (wavelength, V3::new(r, g, b)))
+            })
+            .collect()
+    })
+}
+
+fn color(nodes: &[(M, V3)], coefficients: V3) -> V3 {
+    nodes.iter().fold(V3::new(0.0, 0.0, 0.0), |rgb, &(wavelength, node)| {
+        rgb + node * sigmoid(polynomial(coefficients, wavelength))
+    })
+}
+
+// columns of the jacobian of the color by the coefficients
+fn jacobian(nodes: &[(M, V3)], coefficients: V3) -> (V3, V3, V3) {
+    let zero = V3::new(0.0, 0.0, 0.0);
+    nodes.iter().fold((zero, zero, zero), |(a, b, c), &(wavelength, node)| {
+        let x = position(wavelength);
+        let z = polynomial(coefficients, wavelength);
+        let derivative = node * (0.5 / (1.0 + z * z).powf(1.5));
+        (a + derivative * (x * x), b + derivative * x, c + derivative)
+    })
+}
+
+fn fit(target: V3) -> V3 {
+    static FITS: OnceLock<Mutex<HashMap<[u64; 3], V3>>> = OnceLock::new();
+    let fits = FITS.get_or_init(Mutex::default);
+    let key = [target.x().to_bits(), target.y().to_bits(), target.z().to_bits()];
+    if let Some(&coefficients) = fits.lock().unwrap().get(&key) {
+        return coefficients;
+    }
+
+    let coefficients = solve(nodes(), target);
+    fits.lock().unwrap().insert(key, coefficients);
+    coefficients
+}
+
+// Newton's method moving the target from the gray toward the color,
+// so the solution for the previous target is a good start for the next one
+fn solve(nodes: &[(M, V3)], target: V3) -> V3 {
+    let gray = V3::new(0.5, 0.5, 0.5);
+    let steps = 8;
+
+    let mut coefficients = V3::new(0.0, 0.0, 0.0);
+    for step in 1..(steps + 1) {
+        let t = (step as M) / (steps as M);
+        let target = gray * (1.0 - t) + target * t;
+        for _ in 0..16 {
+            let residual = color(nodes, coefficients) - target;
+            if residual.length() < 1.0e-6 {
+                break;
+            }
+
+            let (a, b, c) = jacobian(nodes, coefficients);
+            let determinant = a.cross(b) * c;
+            if determinant.abs() < 1.0e-12 {
+                break;
+            }
+
+            // rows of the inverse jacobian are the adjugated columns
+            let (ia, ib, ic) = V3::adj(a, b, c);
+            let delta = V3::new(ia * residual, ib * residual, ic * residual) / determinant;
+
+            // halve the step while it does not improve the color
+            let mut scale = 1.0;
+            while scale > 1.0e-3 {
+                let candidate = coefficients - delta * scale;
+                if (color(nodes, candidate) - target).length() < residual.length() {
+                    coefficients = candidate;
+                    break;
+                }
+                scale /= 2.0;
+            }
+        }
+    }
+
+    coefficients
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn primaries() {
+        // saturated colors come back from the spectrum lit by D65 and seen by the observer
+        let matching = Observer::Cie1931.matching();
+        let d65 = Beam::d65();
+        let white = XYZ::from_beam(&matching, &d65).y();
+        let colors = [
+            (1.0, 0.0, 0.0),
+            (0.0, 1.0, 0.0),
+            (0.0, 0.0, 1.0),
+            (1.0, 1.0, 0.0),
+            (0.0, 1.0, 1.0),
+            (1.0, 0.0, 1.0),
+            (1.0, 1.0, 1.0),
+            (0.8, 0.6, 0.1),
+        ];
+        for &(r, g, b) in colors.iter() {
+            let beam = Beam::from_srgb(r, g, b);
+            let lit = Beam::from_bands(|f| beam.density(f) * d65.density(f));
+            let xyz = XYZ::from_beam(&matching, &lit) * (1.0 / white);
+            let (er, eg, eb) = ColorSpace::Srgb.encode(&xyz).components();
+            let error = V3::new(er - r, eg - g, eb - b).length();
+            assert!(error < 0.005, "{:?} comes back with the error {}", (r, g, b), error);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let nodes = nodes();
+        let targets = [
+            V3::new(0.214, 0.214, 0.214),
+            V3::new(0.6, 0.3, 0.1),
+            V3::new(0.1, 0.4, 0.7),
+        ];
+        for &target in targets.iter() {
+            let coefficients = fit(target);
+            assert!((color(nodes, coefficients) - target).length() < 1.0e-3);
+            assert!((fit(target) - coefficients).length() == 0.0);
+        }
+
+        let white = Beam::from_srgb(1.0, 1.0, 1.0);
+        for frequency in Frequency::all() {
+            assert!(white.density(&frequency) > 0.95);
+        }
+    }
+}
@@ -0,0 +1,85 @@
+extern crate gus;
+extern crate rand;
+
+use gus::Beam;
+use gus::Frequency;
+use gus::GeometricalRay;
+use gus::Integrator;
+use gus::Material;
+use gus::Method;
+use gus::PhotonicRay;
+use gus::Ray;
+use gus::Scene;
+use gus::Sphere;
+use gus::Triangle;
+use gus::V3;
+
+use std::ptr;
+
+use rand::Rng;
+use rand::distributions::Sample;
+use rand::distributions::Range;
+
+// the emission seen directly and reflected once toward the random emitter
+struct Direct;
+
+impl Integrator for Direct {
+    fn trace(&self, scene: &Scene, ray: &Ray, mut rng: &mut dyn Rng) -> f64 {
+        let result = match scene.intersect(ray) {
+            Some(result) => result,
+            None => return 0.0,
+        };
+
+        let bsdf = result.surface.bsdf();
+        let frequency = ray.frequency();
+        let count = scene.emitter_count();
+        if count == 0 {
+            return bsdf.emission(&frequency);
+        }
+
+        let emitter = scene.emitter(Range::new(0, count).sample(&mut rng));
+        let direction = match emitter.sample_direction(result.position, &mut rng) {
+            Some((direction, _)) => direction,
+            None => return bsdf.emission(&frequency),
+        };
+
+        let position = result.position + direction * Ray::EPS;
+        let shadow = Ray::new(position, direction, frequency.clone());
+        let reflected = match scene.intersect(&shadow) {
+            Some(ref light) if ptr::eq(light.surface, emitter.surface()) => {
+                let pdf = scene.light_pdf(light.surface, result.position, direction);
+                let value = bsdf.evaluate(ray.direction(), direction, result.normal, &frequency);
+                if pdf > 0.0 {
+                    light.surface.bsdf().emission(&frequency) * value / pdf
+                } else {
+                    0.0
+                }
+            }
+            _ => 0.0,
+        };
+
+        bsdf.emission(&frequency) + reflected
+    }
+}
+
+#[test]
+fn custom() {
+    // white lambertian floor under the spherical lamp, the light is seen in the cone
+    let mut rng = rand::thread_rng();
+    let lamp = Sphere::new(V3::new(0.0, 0.0, 2.0), 1.0, Material::emission(Beam::flat(1.0)));
+    let floor = Triangle::new(
+        V3::new(-100.0, -100.0, 0.0),
+        V3::new(100.0, -100.0, 0.0),
+        V3::new(0.0, 100.0, 0.0),
+        Material::diffuse(Beam::flat(1.0)),
+    );
+    let scene = Scene::new(vec![lamp], vec![floor]).with_method(Method::custom(Direct));
+
+    let samples = 20000;
+    let direction = V3::new(-1.0, 0.0, -1.0).normalize();
+    let ray = Ray::new(V3::new(3.0, 0.0, 3.0), direction, Frequency::new(0));
+    let sum = (0..samples).fold(0.0, |sum, _| sum + scene.trace(&ray, &mut rng));
+
+    let expected = 1.0 - 3.0_f64.sqrt() / 2.0;
+    assert!((sum / (samples as f64) - expected).abs() < 0.01);
+}
@@ -0,0 +1,63 @@
+#![allow(clippy::redundant_field_names)]
+
+extern crate gus;
+extern crate rand;
+
+use gus::Beam;
+use gus::ColorSpace;
+use gus::Eye;
+use gus::Material;
+use gus::Screen;
+use gus::Scene;
+use gus::Size;
+use gus::Spectrum;
+use gus::Sphere;
+use gus::Triangle;
+use gus::V3;
+
+#[test]
+fn wide() {
+    // the spectrum reaching past the color table is configured before any beam exists,
+    // the test runs in its own process, so the rest of the tests keep the visible one
+    let spectrum = Spectrum::new(64, 350.0, 850.0).unwrap();
+    assert!(Spectrum::configure(spectrum).is_ok());
+    assert_eq!(Spectrum::current().size(), 64);
+
+    let mut rng = rand::thread_rng();
+    let lamp = Sphere::new(V3::new(0.0, 0.0, 2.0), 1.0, Material::emission(Beam::d65()));
+    let floor = Triangle::new(
+        V3::new(-100.0, -100.0, 0.0),
+        V3::new(100.0, -100.0, 0.0),
+        V3::new(0.0, 100.0, 0.0),
+        Material::diffuse(Beam::red() * 0.5 + Beam::green() * 0.3),
+    );
+    let scene = Scene::new(vec![lamp], vec![floor]);
+
+    let forward = V3::new(0.0, 3.0, -1.0).normalize();
+    let right = V3::new(1.0, 0.0, 0.0);
+    let eye = Eye {
+        position: V3::new(0.0, -3.0, 1.0),
+        forward: forward,
+        right: right,
+        up: right.cross(forward),
+        width: 0.6,
+        height: 0.6,
+        distance: 1.0,
+        polarizer: None,
+    };
+    let format = Size {
+        horizontal_count: 4,
+        vertical_count: 4,
+    };
+    let screen = Screen::new(format, eye);
+
+    let mut image = screen.create_image();
+    for _ in 0..20 {
+        screen.sample(&scene, &mut image, &mut rng);
+    }
+
+    let channels = image.channels();
+    assert!(channels.iter().all(|pixel| pixel.iter().all(|value| value.is_finite())));
+    assert!(channels.iter().any(|pixel| pixel[1] > 0.0));
+    assert!(image.bitmap(1.0, ColorSpace::Srgb).is_ok());
+}